@@ -0,0 +1,100 @@
+//! The metrics and indicators behind `--list-metrics`.
+//!
+//! Flags, defaults and descriptions of the optional ones come from the clap definitions, so the
+//! list only has to name which args are metrics.
+
+use std::io::Write;
+
+use anyhow::Result;
+use clap::Command;
+use tabled::{builder::Builder, settings::Style};
+
+pub enum Source {
+    /// reported for every ticker, with what it measures
+    Always(&'static str),
+    /// turned on or tuned by the arg with this id
+    Flag(&'static str),
+}
+
+pub const METRICS: &[(&str, Source)] = &[
+    (
+        "Pct change and CAGR",
+        Source::Always("change over the period and its annualized rate"),
+    ),
+    ("Volatility", Source::Flag("vol_estimator")),
+    (
+        "Sortino ratio",
+        Source::Always("return above the risk-free rate per unit of downside risk"),
+    ),
+    (
+        "Sharpe ratio",
+        Source::Always("return above the risk-free rate per unit of total risk"),
+    ),
+    ("Omega ratio", Source::Flag("omega_threshold")),
+    (
+        "Max drawdown",
+        Source::Always("largest fall from a closing peak, and the longest time below one"),
+    ),
+    ("Missing trading days", Source::Flag("exchange")),
+    (
+        "Dollar volume",
+        Source::Always("average and median daily traded value"),
+    ),
+    ("Benchmark stats", Source::Flag("benchmark")),
+    ("Rolling correlation", Source::Flag("rolling_corr")),
+    ("Rolling Sharpe", Source::Flag("rolling_sharpe")),
+    ("Sector comparison", Source::Flag("sector_compare")),
+    ("Ulcer index", Source::Flag("ulcer")),
+    ("Money Flow Index", Source::Flag("mfi")),
+    ("Chande momentum", Source::Flag("cmo")),
+    ("Relative volume", Source::Flag("relative_volume")),
+    ("Keltner channels", Source::Flag("keltner")),
+    ("Bollinger bands", Source::Flag("bollinger")),
+    ("VWAP bands", Source::Flag("vwap_bands")),
+    ("Anchored VWAP", Source::Flag("anchored_vwap")),
+    ("Seasonality", Source::Flag("seasonality")),
+    ("Earnings impact", Source::Flag("earnings_impact")),
+    ("Earnings history", Source::Flag("earnings_history")),
+    ("Earnings markers", Source::Flag("earnings_markers")),
+    ("Position P&L", Source::Flag("cost_basis")),
+    ("Drawdown column", Source::Flag("show_drawdown")),
+    ("Rebased closes", Source::Flag("rebase")),
+    ("SMA crossover backtest", Source::Flag("backtest")),
+];
+
+pub fn print(out: &mut dyn Write, cmd: &Command) -> Result<()> {
+    let mut builder = Builder::default();
+    builder.push_record(["Metric", "Flag", "Default", "Description"]);
+    for (name, source) in METRICS {
+        let (flag, default, description) = match source {
+            Source::Always(description) => {
+                ("-".to_string(), String::new(), description.to_string())
+            }
+            Source::Flag(id) => {
+                let arg = cmd
+                    .get_arguments()
+                    .find(|a| a.get_id() == id)
+                    .ok_or_else(|| anyhow::anyhow!("no arg {} for {}", id, name))?;
+                let help = arg.get_help().map(|h| h.to_string()).unwrap_or_default();
+                // defaults for a flag given without a value are spelled out at the end of the help
+                let (help, noted) = match help.rsplit_once(" [default: ") {
+                    Some((help, noted)) => {
+                        (help.to_string(), noted.trim_end_matches(']').to_string())
+                    }
+                    None => (help, String::new()),
+                };
+                let default = match arg.get_default_values().first() {
+                    Some(value) if arg.get_action().takes_values() => {
+                        value.to_string_lossy().into_owned()
+                    }
+                    _ => noted,
+                };
+                let long = arg.get_long().unwrap_or_default();
+                (format!("--{}", long), default, help)
+            }
+        };
+        builder.push_record([name.to_string(), flag, default, description]);
+    }
+    writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+    Ok(())
+}
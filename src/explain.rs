@@ -0,0 +1,117 @@
+//! Plain-English readings of the headline metrics, printed by `--explain`.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    /// annualized volatility in percent
+    Volatility,
+    Sortino,
+    Sharpe,
+    Omega,
+    /// max drawdown in percent
+    MaxDrawdown,
+    MoneyFlow,
+    Chande,
+    RelativeVolume,
+    Correlation,
+    Beta,
+}
+
+impl Metric {
+    /// What the metric measures.
+    pub fn description(self) -> &'static str {
+        match self {
+            Metric::Volatility => "how widely the price swings over a year",
+            Metric::Sortino => "return above the risk-free rate per unit of downside risk",
+            Metric::Sharpe => "return above the risk-free rate per unit of total risk",
+            Metric::Omega => "gains above the threshold relative to losses below it",
+            Metric::MaxDrawdown => "the largest fall from a peak within the period",
+            Metric::MoneyFlow => "buying versus selling pressure, weighted by volume",
+            Metric::Chande => "the balance of up moves against down moves",
+            Metric::RelativeVolume => "the latest volume against its recent average",
+            Metric::Correlation => "how closely returns track the benchmark",
+            Metric::Beta => "how far the stock tends to move for each move in the benchmark",
+        }
+    }
+
+    /// A band for `value` and what it means.
+    pub fn band(self, value: f64) -> (&'static str, &'static str) {
+        match self {
+            Metric::Volatility => match value {
+                v if v < 15.0 => ("low", "calmer than most stocks"),
+                v if v < 30.0 => ("moderate", "typical for a large company"),
+                v if v < 50.0 => ("high", "this stock is quite volatile"),
+                _ => ("very high", "expect large day-to-day swings"),
+            },
+            Metric::Sortino | Metric::Sharpe => match value {
+                v if v < 0.0 => ("poor", "returns trailed the risk-free rate"),
+                v if v < 1.0 => ("fair", "some reward for the risk taken"),
+                v if v < 2.0 => ("good", "a solid reward for the risk taken"),
+                _ => (
+                    "excellent",
+                    "strong returns for the risk, though rarely sustained",
+                ),
+            },
+            Metric::Omega => match value {
+                v if v < 1.0 => ("weak", "losses outweighed gains"),
+                v if v < 1.5 => ("fair", "gains slightly outweighed losses"),
+                _ => ("strong", "gains clearly outweighed losses"),
+            },
+            Metric::MaxDrawdown => match value {
+                v if v < 10.0 => ("shallow", "the price held up well"),
+                v if v < 20.0 => ("moderate", "a normal pullback"),
+                v if v < 35.0 => ("deep", "holders sat through a sizeable loss"),
+                _ => ("severe", "a bear-market sized fall"),
+            },
+            Metric::MoneyFlow => match value {
+                v if v >= 80.0 => ("overbought", "heavy buying that may be stretched"),
+                v if v <= 20.0 => ("oversold", "heavy selling that may be stretched"),
+                _ => ("neutral", "no strong pressure either way"),
+            },
+            Metric::Chande => match value {
+                v if v >= 50.0 => ("overbought", "up moves have dominated"),
+                v if v <= -50.0 => ("oversold", "down moves have dominated"),
+                _ => ("neutral", "no strong momentum either way"),
+            },
+            Metric::RelativeVolume => match value {
+                v if v < 0.5 => ("quiet", "well below usual trading"),
+                v if v < 1.5 => ("normal", "in line with usual trading"),
+                v if v < 3.0 => ("elevated", "more interest than usual"),
+                _ => ("unusual", "often news-driven"),
+            },
+            Metric::Correlation => match value.abs() {
+                v if v < 0.3 => ("weak", "moves largely on its own"),
+                v if v < 0.7 => ("moderate", "partly follows the benchmark"),
+                _ => ("strong", "mostly follows the benchmark"),
+            },
+            Metric::Beta => match value {
+                v if v < 0.0 => ("inverse", "tends to move against the benchmark"),
+                v if v < 0.8 => ("defensive", "moves less than the benchmark"),
+                v if v <= 1.2 => ("market-like", "moves about as much as the benchmark"),
+                _ => ("aggressive", "amplifies the benchmark's moves"),
+            },
+        }
+    }
+
+    pub fn explain(self, value: f64) -> String {
+        let (band, meaning) = self.band(value);
+        format!("{}; {} ({})", band, meaning, self.description())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands() {
+        assert_eq!(Metric::Volatility.band(34.0).0, "high");
+        assert_eq!(Metric::Volatility.band(14.9).0, "low");
+        assert_eq!(Metric::Sharpe.band(-0.2).0, "poor");
+        assert_eq!(Metric::Correlation.band(-0.9).0, "strong");
+        assert_eq!(Metric::Beta.band(1.2).0, "market-like");
+        assert_eq!(
+            Metric::Volatility.explain(34.0),
+            "high; this stock is quite volatile (how widely the price swings over a year)"
+        );
+    }
+}
@@ -0,0 +1,2896 @@
+//! Fetches price history for a ticker from Yahoo Finance and computes volatility, risk and
+//! benchmark statistics over it. The `vol` binary is a thin CLI over this crate.
+//!
+//! ```no_run
+//! use vol::{analyze, AnalyzeOptions, QuoteOptions};
+//! use yfinance_rs::{Range, YfClient};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = YfClient::default();
+//! let opts = AnalyzeOptions {
+//!     quotes: QuoteOptions {
+//!         range: Range::Y1,
+//!         ..Default::default()
+//!     },
+//!     benchmark: vec!["SPY".to_string()],
+//!     ..Default::default()
+//! };
+//! let analysis = analyze(&client, "MSFT", opts).await?;
+//! println!(
+//!     "{} candles, annualized volatility {:?}, beta {:?}",
+//!     analysis.report.quotes.len(),
+//!     analysis.metrics.annualized_vol,
+//!     analysis.metrics.beta
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use chrono::{Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use paft_money::Money;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use statrs::statistics::Statistics;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use yfinance_rs::core::conversions::money_to_f64;
+use yfinance_rs::core::{RetryConfig, YfError};
+use yfinance_rs::fundamentals::{CashflowRow, Earnings};
+use yfinance_rs::profile::{load_profile, Profile};
+use yfinance_rs::{Candle, Interval, Range, Ticker, YfClient};
+
+const TRADING_DAYS_YEAR: f64 = 252.0; // assume 252 trading days per year
+const TRADING_MINUTES_DAY: f64 = 390.0; // 09:30 to 16:00
+
+// fewer returns than this in a seasonality bucket is reported as n/a
+pub const MIN_SEASON_SAMPLES: usize = 4;
+// candles needed for the pct change and CAGR, and for the return-based stats
+pub const MIN_CANDLES_CHANGE: usize = 2;
+pub const MIN_CANDLES_STATS: usize = 3;
+pub const DEFAULT_TZ: Tz = chrono_tz::America::New_York;
+// series at least this long compute their indicators on separate threads
+pub const PARALLEL_THRESHOLD: usize = 5_000;
+// retries for a rate-limited (429) request, waiting RATE_LIMIT_BACKOFF and doubling each time
+const RATE_LIMIT_RETRIES: u32 = 3;
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(2);
+pub const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// S&P Select Sector SPDRs, keyed by Yahoo's sector names and the GICS ones
+const SECTOR_ETFS: [(&str, &str); 17] = [
+    ("technology", "XLK"),
+    ("information technology", "XLK"),
+    ("financial services", "XLF"),
+    ("financials", "XLF"),
+    ("energy", "XLE"),
+    ("healthcare", "XLV"),
+    ("health care", "XLV"),
+    ("consumer cyclical", "XLY"),
+    ("consumer discretionary", "XLY"),
+    ("consumer defensive", "XLP"),
+    ("consumer staples", "XLP"),
+    ("industrials", "XLI"),
+    ("basic materials", "XLB"),
+    ("materials", "XLB"),
+    ("utilities", "XLU"),
+    ("real estate", "XLRE"),
+    ("communication services", "XLC"),
+];
+// used when the sector is unknown or has no ETF
+pub const SECTOR_FALLBACK_ETF: &str = "SPY";
+
+/// How to fetch price history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuoteOptions {
+    pub range: Range,
+    pub interval: Interval,
+    /// adjust prices for splits and dividends
+    pub auto_adjust: bool,
+    /// include pre-market and post-market candles (intraday intervals only)
+    pub prepost: bool,
+    /// include dividends and splits in the response
+    pub include_actions: bool,
+}
+
+impl Default for QuoteOptions {
+    fn default() -> Self {
+        QuoteOptions {
+            range: Range::M1,
+            interval: Interval::D1,
+            auto_adjust: true,
+            prepost: false,
+            include_actions: true,
+        }
+    }
+}
+
+/// What to fetch and which optional metrics to compute; mirrors the CLI flags.
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions {
+    pub quotes: QuoteOptions,
+    /// exclude extended-hours candles from the price analysis
+    pub regular_hours_only: bool,
+    /// benchmark symbols; the first gets the full beta and correlation breakdown
+    pub benchmark: Vec<String>,
+    pub rolling_corr: Option<u16>,
+    pub rolling_sharpe: Option<u16>,
+    /// annualize CAGR by calendar days / 365 instead of trading periods
+    pub calendar_annualization: bool,
+    /// compute stats over only the trailing N returns
+    pub stats_window: Option<u32>,
+    pub ulcer: bool,
+    pub vol_estimator: VolEstimator,
+    pub mfi: Option<u16>,
+    /// Chande Momentum Oscillator period
+    pub cmo: Option<u16>,
+    /// indicator input price; by default Keltner uses the typical price and the rest the close
+    pub price_type: Option<PriceType>,
+    pub relative_volume: Option<u16>,
+    /// (EMA period, ATR period, ATR multiplier) for Keltner channels
+    pub keltner: Option<(u16, u16, f64)>,
+    /// (period, standard deviations) for Bollinger bands
+    pub bollinger: Option<(u16, f64)>,
+    /// width in standard deviations of the session VWAP bands (intraday intervals only)
+    pub vwap_bands: Option<f64>,
+    /// cumulative VWAP from the first candle on or after this date
+    pub anchored_vwap: Option<NaiveDate>,
+    /// (short, long) SMA periods for the crossover backtest
+    pub backtest: Option<(u16, u16)>,
+    /// fraction of each backtest fill's value paid as commission, e.g. 0.001
+    pub commission: f64,
+    /// trading calendar used to check the fetched candles for missing days
+    pub exchange: Exchange,
+    pub omega_threshold: f64,
+    pub earnings_impact: bool,
+    /// fetch reported vs estimated EPS for past quarters and annual earnings
+    pub earnings_history: bool,
+    /// drop candles with volume below this (or no volume)
+    pub min_volume: Option<u64>,
+    pub cost_basis: Option<f64>,
+    pub shares: Option<f64>,
+    pub buy_date: Option<NaiveDate>,
+    pub seasonality: Option<SeasonalityBasis>,
+    /// compare against the ETF for the ticker's sector, ahead of `benchmark`
+    pub sector_compare: bool,
+    /// symbol whose last price is the annualized risk-free rate in percent
+    pub risk_free_ticker: String,
+    /// annualized rate used when the risk-free ticker can't be fetched; 0 when unset
+    pub risk_free_rate: Option<f64>,
+    /// read candles from `<dir>/<SYMBOL>.json` instead of calling Yahoo Finance; the range and
+    /// interval are ignored and there is no quote, earnings or cash flow data
+    pub fixtures: Option<PathBuf>,
+    /// candle count from which independent indicators are computed in parallel
+    pub parallel_threshold: usize,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        AnalyzeOptions {
+            quotes: QuoteOptions::default(),
+            regular_hours_only: false,
+            benchmark: vec![],
+            rolling_corr: None,
+            rolling_sharpe: None,
+            calendar_annualization: false,
+            stats_window: None,
+            ulcer: false,
+            vol_estimator: VolEstimator::Close,
+            mfi: None,
+            cmo: None,
+            price_type: None,
+            relative_volume: None,
+            keltner: None,
+            bollinger: None,
+            vwap_bands: None,
+            anchored_vwap: None,
+            backtest: None,
+            commission: 0.0,
+            exchange: Exchange::Us,
+            omega_threshold: 0.0,
+            earnings_impact: false,
+            earnings_history: false,
+            min_volume: None,
+            cost_basis: None,
+            shares: None,
+            buy_date: None,
+            seasonality: None,
+            sector_compare: false,
+            risk_free_ticker: "^IRX".to_string(),
+            risk_free_rate: None,
+            fixtures: None,
+            parallel_threshold: PARALLEL_THRESHOLD,
+        }
+    }
+}
+
+impl AnalyzeOptions {
+    pub fn prepost(&self) -> bool {
+        self.quotes.prepost && self.quotes.interval.is_intraday()
+    }
+}
+
+/// How annualized volatility is estimated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+#[value(rename_all = "lowercase")]
+pub enum VolEstimator {
+    /// standard deviation of close-to-close returns
+    #[default]
+    Close,
+    /// high/low range
+    Parkinson,
+    /// Garman-Klass, from open, high, low and close
+    Gk,
+    /// every estimator, for comparison
+    All,
+}
+
+/// Price of each candle fed to the Keltner, Bollinger and CMO helpers.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[value(rename_all = "lowercase")]
+pub enum PriceType {
+    Close,
+    /// (high + low + close) / 3
+    Typical,
+    /// (high + low) / 2
+    Median,
+    /// (high + low + 2 * close) / 4, the weighted close
+    Hlc,
+    /// (open + high + low + close) / 4
+    Ohlc4,
+}
+
+impl PriceType {
+    pub fn of(self, q: &Candle) -> f64 {
+        let (o, h, l, c) = (
+            money_to_f64(&q.open),
+            money_to_f64(&q.high),
+            money_to_f64(&q.low),
+            money_to_f64(&q.close),
+        );
+        match self {
+            PriceType::Close => c,
+            PriceType::Typical => (h + l + c) / 3.0,
+            PriceType::Median => (h + l) / 2.0,
+            PriceType::Hlc => (h + l + 2.0 * c) / 4.0,
+            PriceType::Ohlc4 => (o + h + l + c) / 4.0,
+        }
+    }
+}
+
+/// Trading calendar of an exchange.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[value(rename_all = "lowercase")]
+pub enum Exchange {
+    /// NYSE and Nasdaq
+    Us,
+    /// every weekday, with no holidays
+    Weekdays,
+}
+
+impl Exchange {
+    /// Full-day market closures in `year`, on the weekday they are observed.
+    pub fn holidays(self, year: i32) -> Vec<NaiveDate> {
+        match self {
+            Exchange::Us => us_holidays(year),
+            Exchange::Weekdays => vec![],
+        }
+    }
+
+    /// Trading days from `from` to `to` inclusive.
+    pub fn trading_days(self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let holidays: BTreeSet<NaiveDate> = (from.year()..=to.year())
+            .flat_map(|year| self.holidays(year))
+            .collect();
+        from.iter_days()
+            .take_while(|d| *d <= to)
+            .filter(|d| d.weekday().number_from_monday() <= 5 && !holidays.contains(d))
+            .collect()
+    }
+}
+
+fn us_holidays(year: i32) -> Vec<NaiveDate> {
+    let date = |month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+    let nth =
+        |month, weekday, n| NaiveDate::from_weekday_of_month_opt(year, month, weekday, n).unwrap();
+    // weekend holidays move to the nearest weekday
+    let observed = |d: NaiveDate| match d.weekday() {
+        Weekday::Sat => d.pred_opt().unwrap(),
+        Weekday::Sun => d.succ_opt().unwrap(),
+        _ => d,
+    };
+    let last_monday_may = (25..=31)
+        .map(|day| date(5, day))
+        .find(|d| d.weekday() == Weekday::Mon)
+        .unwrap();
+    let mut holidays = vec![
+        easter(year) - chrono::Duration::days(2),
+        observed(date(7, 4)),
+        nth(9, Weekday::Mon, 1),
+        nth(11, Weekday::Thu, 4),
+        observed(date(12, 25)),
+    ];
+    // a Saturday New Year isn't moved back into the previous year
+    if date(1, 1).weekday() != Weekday::Sat {
+        holidays.push(observed(date(1, 1)));
+    }
+    if year >= 1998 {
+        holidays.push(nth(1, Weekday::Mon, 3));
+    }
+    // the Uniform Monday Holiday Act moved these from fixed dates in 1971
+    if year >= 1971 {
+        holidays.push(nth(2, Weekday::Mon, 3));
+        holidays.push(last_monday_may);
+    } else {
+        holidays.push(observed(date(2, 22)));
+        holidays.push(observed(date(5, 30)));
+    }
+    // Election Day every year until 1968, then in presidential years only until 1980
+    if year <= 1968 || (year <= 1980 && year % 4 == 0) {
+        holidays.push(nth(11, Weekday::Mon, 1) + chrono::Duration::days(1));
+    }
+    if year >= 2022 {
+        holidays.push(observed(date(6, 19)));
+    }
+    holidays.extend(
+        US_CLOSURES
+            .iter()
+            .filter(|(y, _, _)| *y == year)
+            .map(|(_, month, day)| date(*month, *day)),
+    );
+    holidays.sort();
+    holidays
+}
+
+// one-off NYSE closures: national days of mourning, storms, blackouts and September 11th
+const US_CLOSURES: &[(i32, u32, u32)] = &[
+    (1963, 11, 25),
+    (1968, 4, 9),
+    (1969, 3, 31),
+    (1969, 7, 21),
+    (1972, 12, 28),
+    (1973, 1, 25),
+    (1977, 7, 14),
+    (1985, 9, 27),
+    (1994, 4, 27),
+    (2001, 9, 11),
+    (2001, 9, 12),
+    (2001, 9, 13),
+    (2001, 9, 14),
+    (2004, 6, 11),
+    (2007, 1, 2),
+    (2012, 10, 29),
+    (2012, 10, 30),
+    (2018, 12, 5),
+    (2025, 1, 9),
+];
+
+fn easter(year: i32) -> NaiveDate {
+    // anonymous Gregorian algorithm
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).unwrap()
+}
+
+/// Trading days between the first and last candle that have no candle.
+#[derive(Debug, Clone, Serialize)]
+pub struct Coverage {
+    pub expected: usize,
+    pub missing: Vec<NaiveDate>,
+}
+
+/// The fetched candles for a ticker together with every metric computed from them.
+#[derive(Debug)]
+pub struct Analysis {
+    pub report: Report,
+    pub metrics: Metrics,
+}
+
+/// Fetches `symbol`, its benchmarks and the risk-free rate, and computes the metrics
+/// selected by `opts`.
+pub async fn analyze(client: &YfClient, symbol: &str, opts: AnalyzeOptions) -> Result<Analysis> {
+    let (shared, report) = tokio::join!(
+        fetch_shared(client, &opts),
+        fetch_report(client, symbol, &opts)
+    );
+    let (shared, report) = (shared?, report?);
+    let metrics = calc_metrics(&report, &opts, &shared);
+    Ok(Analysis { report, metrics })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Session {
+    Pre,
+    Regular,
+    Post,
+}
+
+impl Session {
+    pub fn label(self) -> &'static str {
+        match self {
+            Session::Pre => "pre",
+            Session::Regular => "regular",
+            Session::Post => "post",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnualizationBasis {
+    Trading,
+    Calendar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lowercase")]
+pub enum SeasonalityBasis {
+    /// candle returns by day of the week
+    Weekday,
+    /// calendar-month returns by month, across years
+    Month,
+}
+
+impl SeasonalityBasis {
+    /// Month for weekly or longer candles, weekday otherwise.
+    pub fn for_interval(interval: Interval) -> Self {
+        match interval {
+            Interval::D5 | Interval::W1 | Interval::M1 | Interval::M3 => SeasonalityBasis::Month,
+            _ => SeasonalityBasis::Weekday,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PriceRange {
+    pub low: f64,
+    pub high: f64,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FetchTimings {
+    pub quotes: Duration,
+    pub earnings: Duration,
+    pub total: Duration,
+}
+
+/// Everything fetched for a single ticker.
+#[derive(Debug)]
+pub struct Report {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub last: Option<f64>,
+    pub quotes: Vec<Candle>,
+    pub tz: Option<Tz>,
+    pub sessions: Vec<Session>,
+    pub returns: Vec<f64>,
+    pub earnings: Option<Vec<DateTime<Utc>>>,
+    // fetched only for --earnings-history; empty when the ticker has none
+    pub earnings_history: Option<Earnings>,
+    pub cashflow: Vec<CashflowRow>,
+    pub timings: FetchTimings,
+    // candles removed by --min-volume
+    pub filtered: usize,
+    // None for intervals longer than a day
+    pub coverage: Option<Coverage>,
+    pub sector: Option<String>,
+    // the sector ETF, fetched only for --sector-compare
+    pub sector_benchmark: Option<Benchmark>,
+}
+
+/// Where the risk-free rate for a run came from.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateSource {
+    Ticker(String),
+    Fallback,
+    // neither the ticker nor a fallback rate was available
+    #[default]
+    Zero,
+}
+
+/// Data fetched once and shared by every ticker in a run.
+#[derive(Debug)]
+pub struct Shared {
+    pub risk_free_rate: f64,
+    pub risk_free_source: RateSource,
+    pub benchmarks: Vec<Benchmark>,
+}
+
+#[derive(Debug)]
+pub struct Benchmark {
+    pub symbol: String,
+    // None when the fetch failed, so the benchmark reports n/a instead of aborting the run
+    pub quotes: Option<Vec<Candle>>,
+    pub tz: Option<Tz>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchmarkStats {
+    pub symbol: String,
+    pub beta: Option<f64>,
+    pub correlation: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoneyFlowIndex {
+    pub period: usize,
+    pub value: Option<f64>,
+    // candles dropped because they had no volume
+    pub skipped: usize,
+}
+
+/// Closes of several tickers rebased to 100 on their latest common start.
+#[derive(Debug, Serialize)]
+pub struct Rebased {
+    pub start: DateTime<Utc>,
+    pub symbols: Vec<String>,
+    // one row per date (or candle time, intraday) from the start on; None where a ticker has no candle
+    pub rows: Vec<(DateTime<Utc>, Vec<Option<f64>>)>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DrawdownDuration {
+    // candles spent below the prior peak in the longest drawdown
+    pub longest: usize,
+    // the peak it fell from and the candle that recovered it (or the last candle)
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub recovered: bool,
+    // the peak the current drawdown fell from, None at a closing high
+    pub underwater_since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChandeMomentum {
+    pub period: usize,
+    pub value: Option<f64>,
+}
+
+/// Liquidity over the window, as close times volume per candle.
+#[derive(Debug, Serialize)]
+pub struct DollarVolume {
+    pub average: f64,
+    pub median: f64,
+    // candles dropped because they had no volume
+    pub skipped: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelativeVolume {
+    pub window: usize,
+    pub value: Option<f64>,
+    // candles in the window left out of the average because they had no volume
+    pub skipped: usize,
+}
+
+/// A price band at one candle; `None` during the indicator's warm-up.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BandPoint {
+    pub ts: DateTime<Utc>,
+    pub lower: Option<f64>,
+    pub middle: Option<f64>,
+    pub upper: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Keltner {
+    pub ema_period: usize,
+    pub atr_period: usize,
+    pub multiplier: f64,
+    pub lower: Option<f64>,
+    pub middle: Option<f64>,
+    pub upper: Option<f64>,
+    pub close: Option<f64>,
+    pub points: Vec<BandPoint>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Bollinger {
+    pub period: usize,
+    pub k: f64,
+    pub points: Vec<BandPoint>,
+}
+
+/// Candles where the Bollinger bands sit inside the Keltner channel.
+#[derive(Debug, Serialize)]
+pub struct Squeeze {
+    pub points: Vec<DateTime<Utc>>,
+    // whether the latest candle is in a squeeze
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VwapPoint {
+    pub ts: DateTime<Utc>,
+    // None until the session has traded any volume
+    pub vwap: Option<f64>,
+    pub upper: Option<f64>,
+    pub lower: Option<f64>,
+}
+
+/// VWAP accumulated from an anchor candle to the latest one.
+#[derive(Debug, Serialize)]
+pub struct AnchoredVwap {
+    // ts of the anchor candle
+    pub anchor: DateTime<Utc>,
+    pub vwap: Option<f64>,
+    pub close: f64,
+    // close relative to the VWAP, in percent
+    pub deviation_pct: Option<f64>,
+}
+
+/// Session VWAP with bands `k` volume-weighted standard deviations either side.
+#[derive(Debug, Serialize)]
+pub struct VwapBands {
+    pub k: f64,
+    pub points: Vec<VwapPoint>,
+    // candles closing outside the bands
+    pub above: usize,
+    pub below: usize,
+}
+
+/// A round trip in the crossover backtest.
+#[derive(Debug, Serialize)]
+pub struct Trade {
+    pub entry: DateTime<Utc>,
+    pub entry_price: f64,
+    // None while the position is still open at the last candle
+    pub exit: Option<DateTime<Utc>>,
+    pub exit_price: f64,
+    // after commission, in percent
+    pub return_pct: f64,
+}
+
+/// Long-only SMA crossover backtest over the analyzed candles, against buy-and-hold.
+#[derive(Debug, Serialize)]
+pub struct Backtest {
+    pub short: usize,
+    pub long: usize,
+    pub commission: f64,
+    pub trades: Vec<Trade>,
+    // percent, like the other returns and drawdowns
+    pub total_return: f64,
+    pub win_rate: Option<f64>,
+    pub max_drawdown: f64,
+    pub hold_return: f64,
+    pub hold_max_drawdown: f64,
+    // strategy equity at each close, starting from 1
+    pub equity: Vec<SeriesPoint>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EarningsEvent {
+    pub earnings: DateTime<Utc>,
+    // first candle on or after the earnings date
+    pub trading_day: DateTime<Utc>,
+    pub day_return: Option<f64>,
+    pub next_day_return: Option<f64>,
+    // which of the two returns the report moved the price in
+    pub reaction: Reaction,
+}
+
+/// The session a report is priced in: the trading day itself for reports before or during the
+/// session, the next one for reports after the close.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reaction {
+    Day,
+    NextDay,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EarningsImpact {
+    pub events: Vec<EarningsEvent>,
+    pub avg_abs_move: Option<f64>,
+    pub up: usize,
+    pub down: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EpsSurprise {
+    pub period: String,
+    pub reported: Option<f64>,
+    pub estimate: Option<f64>,
+    // relative to the size of the estimate
+    pub surprise_pct: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnnualEarnings {
+    pub year: i32,
+    pub revenue: Option<f64>,
+    pub earnings: Option<f64>,
+}
+
+/// Past results reported by `--earnings-history`; both lists are empty for ETFs and indices.
+#[derive(Debug, Serialize)]
+pub struct EarningsHistory {
+    pub quarters: Vec<EpsSurprise>,
+    pub years: Vec<AnnualEarnings>,
+}
+
+/// Unrealized P&L of a position against the latest close, reported by `--cost-basis`.
+#[derive(Debug, Serialize)]
+pub struct Position {
+    pub cost_basis: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shares: Option<f64>,
+    pub pnl_pct: f64,
+    // per share unless shares were given
+    pub pnl: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_date: Option<NaiveDate>,
+    // ts of the buy date's candle, or the candle nearest after (before, past the last one)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_candle: Option<DateTime<Utc>>,
+    // the buy candle's date in exchange time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_candle_date: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since_buy: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeasonBucket {
+    pub label: String,
+    pub count: usize,
+    pub avg_return: Option<f64>,
+    pub win_rate: Option<f64>,
+}
+
+/// Calendar-month returns for one year, January first; `None` for months without data.
+#[derive(Debug, Serialize)]
+pub struct SeasonYear {
+    pub year: i32,
+    pub months: [Option<f64>; 12],
+}
+
+#[derive(Debug, Serialize)]
+pub struct Seasonality {
+    pub basis: SeasonalityBasis,
+    pub buckets: Vec<SeasonBucket>,
+    // the month-by-year heatmap; empty for the weekday basis
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub years: Vec<SeasonYear>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeriesPoint {
+    pub ts: DateTime<Utc>,
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RollingSeries {
+    pub window: usize,
+    pub points: Vec<SeriesPoint>,
+}
+
+/// Metrics left out because the window had too few candles.
+#[derive(Debug, Serialize)]
+pub struct Skipped {
+    pub metrics: &'static str,
+    pub need: usize,
+    pub have: usize,
+}
+
+/// Summary statistics computed from a report; `None` when there isn't enough data.
+#[derive(Debug, Default, Serialize)]
+pub struct Metrics {
+    pub pct_change: Option<f64>,
+    pub cagr: Option<f64>,
+    pub cagr_basis: Option<AnnualizationBasis>,
+    pub std_dev: Option<f64>,
+    pub annualized_vol: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parkinson_vol: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub garman_klass_vol: Option<f64>,
+    pub sortino: Option<f64>,
+    pub sharpe: Option<f64>,
+    pub omega: Option<f64>,
+    pub omega_threshold: f64,
+    pub risk_free_rate: f64,
+    pub risk_free_source: RateSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_window: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<Skipped>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<Coverage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filtered_candles: Option<usize>,
+    pub max_drawdown: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drawdown_duration: Option<DrawdownDuration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backtest: Option<Backtest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ulcer_index: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mfi: Option<MoneyFlowIndex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmo: Option<ChandeMomentum>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative_volume: Option<RelativeVolume>,
+    pub dollar_volume: Option<DollarVolume>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keltner: Option<Keltner>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bollinger: Option<Bollinger>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squeeze: Option<Squeeze>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vwap_bands: Option<VwapBands>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchored_vwap: Option<AnchoredVwap>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earnings_impact: Option<EarningsImpact>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earnings_history: Option<EarningsHistory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seasonality: Option<Seasonality>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sector: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sector_etf: Option<String>,
+    pub intraday_range: Option<PriceRange>,
+    pub closing_range: Option<PriceRange>,
+    pub pct_below_intraday_high: Option<f64>,
+    pub pct_below_closing_high: Option<f64>,
+    pub earnings_date: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub benchmark: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beta: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up_beta: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down_beta: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upside_capture: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downside_capture: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rolling_corr: Option<RollingSeries>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub benchmarks: Vec<BenchmarkStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rolling_sharpe: Option<RollingSeries>,
+}
+
+pub async fn timed<T>(fut: impl Future<Output = T>) -> (T, Duration) {
+    let start = Instant::now();
+    let res = fut.await;
+    (res, start.elapsed())
+}
+
+/// ETF for a sector name such as "Technology", or SPY when there is none.
+pub fn sector_etf(sector: Option<&str>) -> &'static str {
+    sector
+        .and_then(|sector| {
+            let sector = sector.trim().to_lowercase();
+            SECTOR_ETFS
+                .iter()
+                .find(|(name, _)| *name == sector)
+                .map(|(_, etf)| *etf)
+        })
+        .unwrap_or(SECTOR_FALLBACK_ETF)
+}
+
+async fn fetch_benchmark(client: &YfClient, symbol: &str, opts: &AnalyzeOptions) -> Benchmark {
+    let quotes = match &opts.fixtures {
+        Some(dir) => load_fixture(dir, symbol).map(|quotes| (quotes, None)),
+        None => {
+            let ticker = Ticker::new(client, symbol);
+            // benchmarks are compared over the regular session only
+            let options = QuoteOptions {
+                prepost: false,
+                ..opts.quotes
+            };
+            get_quotes(&ticker, &options).await
+        }
+    };
+    if let Err(e) = &quotes {
+        eprintln!("Could not fetch benchmark {}: {}", symbol, e);
+    }
+    let (quotes, tz) = match quotes {
+        Ok((quotes, tz)) => (Some(quotes), tz),
+        Err(_) => (None, None),
+    };
+    Benchmark {
+        symbol: symbol.to_uppercase(),
+        quotes,
+        tz,
+    }
+}
+
+async fn get_sector(client: &YfClient, symbol: &str) -> Option<String> {
+    match retry_rate_limited(|| load_profile(client, symbol)).await {
+        Ok(Profile::Company(company)) => company.sector,
+        // funds have no sector
+        _ => None,
+    }
+}
+
+pub async fn fetch_shared(client: &YfClient, opts: &AnalyzeOptions) -> Result<Shared> {
+    let benchmarks = futures::future::join_all(
+        opts.benchmark
+            .iter()
+            .map(|symbol| fetch_benchmark(client, symbol, opts)),
+    );
+    let rate = async {
+        match &opts.fixtures {
+            Some(dir) => {
+                let quotes = load_fixture(dir, &opts.risk_free_ticker)?;
+                let last = quotes.last().context("empty risk-free fixture")?;
+                Ok(money_to_f64(&last.close) / 100.0)
+            }
+            None => get_risk_free_rate(client, &opts.risk_free_ticker).await,
+        }
+    };
+    let (rate, benchmarks) = tokio::join!(rate, benchmarks);
+    let (risk_free_rate, risk_free_source) = match (rate, opts.risk_free_rate) {
+        (Ok(rate), _) => (
+            rate,
+            RateSource::Ticker(opts.risk_free_ticker.to_uppercase()),
+        ),
+        (Err(e), fallback) => {
+            let (rate, source) = match fallback {
+                Some(rate) => (rate, RateSource::Fallback),
+                None => (0.0, RateSource::Zero),
+            };
+            eprintln!(
+                "Could not fetch risk-free rate from {}: {}; using {:.2}%",
+                opts.risk_free_ticker,
+                e,
+                rate * 100.0
+            );
+            (rate, source)
+        }
+    };
+    Ok(Shared {
+        risk_free_rate,
+        risk_free_source,
+        benchmarks,
+    })
+}
+
+pub async fn fetch_report(
+    client: &YfClient,
+    symbol: &str,
+    opts: &AnalyzeOptions,
+) -> Result<Report> {
+    let (mut quotes, tz, name, last, earnings, history, cashflow, sector, timings) = match &opts
+        .fixtures
+    {
+        Some(dir) => {
+            // recorded candles only; there is no quote, profile, earnings or cash flow data offline
+            let quotes = load_fixture(dir, symbol)?;
+            let last = quotes.last().map(|q| money_to_f64(&q.close));
+            (
+                quotes,
+                None,
+                None,
+                last,
+                None,
+                opts.earnings_history.then(Earnings::default),
+                vec![],
+                None,
+                FetchTimings::default(),
+            )
+        }
+        None => {
+            let ticker = Ticker::new(client, symbol);
+            let start = Instant::now();
+            let ((quotes, quotes_time), (earnings, earnings_time), history, fi, cf, sector) = tokio::join!(
+                timed(get_quotes(&ticker, &opts.quotes)),
+                timed(get_earnings_dates(&ticker)),
+                async {
+                    if !opts.earnings_history {
+                        return None;
+                    }
+                    // like the benchmarks, a failed fetch is reported and the section left out;
+                    // an empty response (ETFs and indices) still prints as having no history
+                    match retry_rate_limited(|| ticker.earnings(None)).await {
+                        Ok(history) => Some(history),
+                        Err(e) => {
+                            eprintln!(
+                                "Could not fetch earnings history for {}: {}",
+                                symbol.to_uppercase(),
+                                e
+                            );
+                            None
+                        }
+                    }
+                },
+                retry_rate_limited(|| ticker.fast_info()),
+                retry_rate_limited(|| ticker.cashflow(None)),
+                async {
+                    match opts.sector_compare {
+                        true => get_sector(client, symbol).await,
+                        false => None,
+                    }
+                },
+            );
+            let timings = FetchTimings {
+                quotes: quotes_time,
+                earnings: earnings_time,
+                total: start.elapsed(),
+            };
+            let fi = fi?;
+            let (quotes, tz) = quotes?;
+            let last = fi.last.map(|last| money_to_f64(&last));
+            (
+                quotes,
+                tz,
+                fi.name,
+                last,
+                earnings.ok(),
+                history,
+                cf?,
+                sector,
+                timings,
+            )
+        }
+    };
+    let sector_benchmark = match opts.sector_compare {
+        true => Some(fetch_benchmark(client, sector_etf(sector.as_deref()), opts).await),
+        false => None,
+    };
+    // checked before --min-volume so filtered candles don't count as missing
+    let coverage = (opts.quotes.interval.is_intraday() || opts.quotes.interval == Interval::D1)
+        .then(|| calc_coverage(&quotes, tz, opts.exchange))
+        .flatten();
+    let fetched = quotes.len();
+    if let Some(min) = opts.min_volume {
+        // the remaining candles are treated as one contiguous series
+        quotes.retain(|q| q.volume.is_some_and(|v| v >= min));
+    }
+    let filtered = fetched - quotes.len();
+    if let (Some(first), Some(last)) = (quotes.first(), quotes.last()) {
+        let (first, last) = (candle_day(first.ts, tz), candle_day(last.ts, tz));
+        for (what, date) in [("buy", opts.buy_date), ("anchor", opts.anchored_vwap)] {
+            if let Some(date) = date.filter(|d| *d < first || *d > last) {
+                anyhow::bail!(
+                    "{} date {} for {} is outside the fetched window {} to {}; use a longer --range",
+                    what,
+                    date,
+                    symbol.to_uppercase(),
+                    first,
+                    last
+                );
+            }
+        }
+    }
+    let sessions = quotes.iter().map(|q| session_of(q.ts, tz)).collect();
+    let returns = calc_returns(&quotes);
+
+    Ok(Report {
+        symbol: symbol.to_uppercase(),
+        name,
+        last,
+        quotes,
+        tz,
+        sessions,
+        returns,
+        earnings,
+        cashflow,
+        timings,
+        filtered,
+        coverage,
+        earnings_history: history,
+        sector,
+        sector_benchmark,
+    })
+}
+
+pub fn calc_metrics(report: &Report, opts: &AnalyzeOptions, shared: &Shared) -> Metrics {
+    let risk_free_rate = shared.risk_free_rate;
+    let mut metrics = Metrics {
+        risk_free_rate,
+        risk_free_source: shared.risk_free_source.clone(),
+        omega_threshold: opts.omega_threshold,
+        filtered_candles: opts.min_volume.map(|_| report.filtered),
+        coverage: report.coverage.clone(),
+        earnings_date: report.earnings.as_ref().and_then(|er| er.first().copied()),
+        ..Default::default()
+    };
+
+    // stats run over the regular session only when asked to; the table always shows everything
+    let (mut quotes, mut returns) = if opts.regular_hours_only && opts.prepost() {
+        let regular: Vec<Candle> = report
+            .quotes
+            .iter()
+            .zip(&report.sessions)
+            .filter(|(_, s)| **s == Session::Regular)
+            .map(|(q, _)| q.clone())
+            .collect();
+        let returns = calc_returns(&regular);
+        (regular, returns)
+    } else {
+        (report.quotes.clone(), report.returns.clone())
+    };
+    if let Some(window) = opts.stats_window {
+        // n returns need n + 1 closes
+        let window = window as usize;
+        if window < returns.len() {
+            quotes.drain(..quotes.len() - window - 1);
+            returns.drain(..returns.len() - window);
+            metrics.stats_window = Some(window);
+        }
+    }
+    let periods_year = periods_per_year(opts.quotes.interval);
+
+    let skipped = &mut metrics.skipped;
+    let has_change = enough_data(skipped, "pct change/CAGR", MIN_CANDLES_CHANGE, quotes.len());
+    let has_stats = enough_data(
+        skipped,
+        "volatility/Sortino/Sharpe/Omega",
+        MIN_CANDLES_STATS,
+        quotes.len(),
+    );
+
+    if has_change {
+        let initial_close = quotes[0].close.amount();
+        if initial_close != Decimal::ZERO {
+            let pct_chg = Decimal::from(100)
+                * (quotes[quotes.len() - 1].close.amount() - initial_close)
+                / initial_close;
+            metrics.pct_change = pct_chg.to_f64();
+        }
+
+        let basis = if opts.calendar_annualization {
+            AnnualizationBasis::Calendar
+        } else {
+            AnnualizationBasis::Trading
+        };
+        metrics.cagr = calc_cagr(&quotes, basis, periods_year);
+        metrics.cagr_basis = metrics.cagr.map(|_| basis);
+    }
+
+    if has_stats {
+        metrics.std_dev = Some(returns.as_slice().std_dev());
+        metrics.annualized_vol = close_vol(&returns, periods_year);
+        metrics.sortino = Some(sortino_ratio(&returns, risk_free_rate, periods_year));
+        // a single window over every return
+        metrics.sharpe = rolling_sharpe(&returns, returns.len(), risk_free_rate, periods_year)
+            .pop()
+            .flatten();
+        metrics.omega = omega(&returns, opts.omega_threshold);
+    }
+
+    let rolling_sharpe_window = opts.rolling_sharpe.map(|w| w as usize).filter(|&window| {
+        // n returns need n + 1 closes
+        enough_data(
+            &mut metrics.skipped,
+            "rolling Sharpe",
+            window + 1,
+            quotes.len(),
+        )
+    });
+    if let Some(window) = rolling_sharpe_window {
+        let values = rolling_sharpe(&returns, window, risk_free_rate, periods_year);
+        metrics.rolling_sharpe = Some(RollingSeries {
+            window,
+            // returns[i] is the move into quotes[i + 1]
+            points: quotes[window.min(quotes.len())..]
+                .iter()
+                .zip(values)
+                .map(|(q, value)| SeriesPoint { ts: q.ts, value })
+                .collect(),
+        });
+    }
+
+    if enough_data(
+        &mut metrics.skipped,
+        "max drawdown",
+        MIN_CANDLES_CHANGE,
+        quotes.len(),
+    ) {
+        metrics.max_drawdown = calc_max_drawdown(&quotes);
+        metrics.drawdown_duration = calc_drawdown_duration(&quotes);
+    }
+    if let Some((short, long)) = opts.backtest {
+        // a cross needs both SMAs on two candles, then another candle to fill on
+        let need = long as usize + 2;
+        if enough_data(&mut metrics.skipped, "backtest", need, quotes.len()) {
+            metrics.backtest = Some(calc_backtest(
+                &quotes,
+                opts.price_type.unwrap_or(PriceType::Close),
+                short as usize,
+                long as usize,
+                opts.commission,
+            ));
+        }
+    }
+    if opts.ulcer
+        && enough_data(
+            &mut metrics.skipped,
+            "ulcer index",
+            MIN_CANDLES_CHANGE,
+            quotes.len(),
+        )
+    {
+        metrics.ulcer_index = calc_ulcer_index(&quotes);
+    }
+
+    if opts.earnings_impact {
+        let dates = report.earnings.as_deref().unwrap_or_default();
+        metrics.earnings_impact = Some(calc_earnings_impact(&quotes, report.tz, dates));
+    }
+
+    if let Some(history) = &report.earnings_history {
+        metrics.earnings_history = Some(calc_earnings_history(history));
+    }
+
+    if let Some(cost_basis) = opts.cost_basis {
+        // the full window, so --stats-window can't push the buy date out of range
+        metrics.position = calc_position(
+            &report.quotes,
+            report.tz,
+            cost_basis,
+            opts.shares,
+            opts.buy_date,
+        );
+    }
+
+    if let Some(basis) = opts.seasonality {
+        metrics.seasonality = Some(calc_seasonality(&quotes, &returns, report.tz, basis));
+    }
+
+    let parallel = quotes.len() >= opts.parallel_threshold;
+    for indicator in run_tasks(
+        indicator_tasks(&quotes, report.tz, opts, periods_year, &mut metrics.skipped),
+        parallel,
+    ) {
+        match indicator {
+            Indicator::Parkinson(vol) => metrics.parkinson_vol = vol,
+            Indicator::GarmanKlass(vol) => metrics.garman_klass_vol = vol,
+            Indicator::Mfi(mfi) => metrics.mfi = Some(mfi),
+            Indicator::Cmo(cmo) => metrics.cmo = Some(cmo),
+            Indicator::Keltner(keltner) => metrics.keltner = Some(keltner),
+            Indicator::Bollinger(bollinger) => metrics.bollinger = Some(bollinger),
+            Indicator::VwapBands(bands) => metrics.vwap_bands = Some(bands),
+        }
+    }
+
+    if let (Some(keltner), Some(bollinger)) = (&metrics.keltner, &metrics.bollinger) {
+        metrics.squeeze = Some(calc_squeeze(&keltner.points, &bollinger.points));
+    }
+
+    if let Some(date) = opts.anchored_vwap {
+        // the full window, like --buy-date
+        let quotes = &report.quotes;
+        let tz = report.tz;
+        if let Some(anchor) = quotes.iter().position(|q| candle_day(q.ts, tz) >= date) {
+            let vwap = calc_anchored_vwap(quotes, anchor).pop().flatten();
+            let close = money_to_f64(&quotes[quotes.len() - 1].close);
+            metrics.anchored_vwap = Some(AnchoredVwap {
+                anchor: quotes[anchor].ts,
+                vwap,
+                close,
+                deviation_pct: vwap
+                    .filter(|v| *v != 0.0)
+                    .map(|v| 100.0 * (close / v - 1.0)),
+            });
+        }
+    }
+
+    let relative_volume_window = opts.relative_volume.map(|w| w as usize).filter(|&window| {
+        // the latest candle against the `window` before it
+        enough_data(
+            &mut metrics.skipped,
+            "relative volume",
+            window + 1,
+            quotes.len(),
+        )
+    });
+    if let Some(window) = relative_volume_window {
+        metrics.relative_volume = Some(calc_relative_volume(&quotes, window));
+    }
+
+    metrics.dollar_volume = calc_dollar_volume(&quotes);
+
+    if let Some((intraday, closing)) = get_price_range(&quotes) {
+        if let Some(last) = report.last {
+            if last < intraday.high {
+                metrics.pct_below_intraday_high =
+                    Some(100.0 * (intraday.high - last) / intraday.high);
+            }
+            if last < closing.high {
+                metrics.pct_below_closing_high = Some(100.0 * (closing.high - last) / closing.high);
+            }
+        }
+        metrics.intraday_range = Some(intraday);
+        metrics.closing_range = Some(closing);
+    }
+
+    // the sector ETF, when there is one, is the primary benchmark
+    let benchmarks: Vec<&Benchmark> = report
+        .sector_benchmark
+        .iter()
+        .chain(&shared.benchmarks)
+        .collect();
+    if let Some(bench) = &report.sector_benchmark {
+        metrics.sector = report.sector.clone();
+        metrics.sector_etf = Some(bench.symbol.clone());
+    }
+    if let Some(bench) = benchmarks.first() {
+        metrics.benchmark = Some(bench.symbol.clone());
+        let pairs = match &bench.quotes {
+            Some(bench_quotes) => aligned_returns(
+                &quotes,
+                report.tz,
+                bench_quotes,
+                bench.tz,
+                opts.quotes.interval,
+            ),
+            None => vec![],
+        };
+        let (xs, ys): (Vec<f64>, Vec<f64>) = pairs.iter().map(|(_, x, y)| (*x, *y)).unzip();
+        metrics.correlation = correlation(&xs, &ys);
+        metrics.beta = beta(&xs, &ys);
+        let (up, down) = split_by_benchmark(&pairs);
+        metrics.up_beta = beta(&up.0, &up.1);
+        metrics.down_beta = beta(&down.0, &down.1);
+        metrics.upside_capture = capture_ratio(&up);
+        metrics.downside_capture = capture_ratio(&down);
+        // n aligned returns come from n + 1 candles matched with the benchmark
+        let matched = if pairs.is_empty() { 0 } else { pairs.len() + 1 };
+        let rolling_corr_window = opts.rolling_corr.map(|w| w as usize).filter(|&window| {
+            enough_data(
+                &mut metrics.skipped,
+                "rolling correlation",
+                window + 1,
+                matched,
+            )
+        });
+        if let Some(window) = rolling_corr_window {
+            metrics.rolling_corr = Some(RollingSeries {
+                window,
+                points: rolling_correlation(&pairs, window),
+            });
+        }
+        metrics.benchmarks.push(BenchmarkStats {
+            symbol: bench.symbol.clone(),
+            beta: metrics.beta,
+            correlation: metrics.correlation,
+        });
+    }
+    for bench in benchmarks.iter().skip(1) {
+        let (beta, correlation) = match &bench.quotes {
+            Some(bench_quotes) => {
+                let pairs = aligned_returns(
+                    &quotes,
+                    report.tz,
+                    bench_quotes,
+                    bench.tz,
+                    opts.quotes.interval,
+                );
+                let (xs, ys): (Vec<f64>, Vec<f64>) = pairs.iter().map(|(_, x, y)| (*x, *y)).unzip();
+                (beta(&xs, &ys), correlation(&xs, &ys))
+            }
+            None => (None, None),
+        };
+        metrics.benchmarks.push(BenchmarkStats {
+            symbol: bench.symbol.clone(),
+            beta,
+            correlation,
+        });
+    }
+
+    metrics
+}
+
+/// One indicator over the candle series; the indicators don't depend on each other, so they run
+/// as separate tasks.
+enum Indicator {
+    Parkinson(Option<f64>),
+    GarmanKlass(Option<f64>),
+    Mfi(MoneyFlowIndex),
+    Cmo(ChandeMomentum),
+    Keltner(Keltner),
+    Bollinger(Bollinger),
+    VwapBands(VwapBands),
+}
+
+type Task<'a> = Box<dyn FnOnce() -> Indicator + Send + 'a>;
+
+fn indicator_tasks<'a>(
+    quotes: &'a [Candle],
+    tz: Option<Tz>,
+    opts: &'a AnalyzeOptions,
+    periods_year: f64,
+    skipped: &mut Vec<Skipped>,
+) -> Vec<Task<'a>> {
+    let mut tasks: Vec<Task> = vec![];
+    if matches!(
+        opts.vol_estimator,
+        VolEstimator::Parkinson | VolEstimator::All
+    ) {
+        tasks.push(Box::new(move || {
+            Indicator::Parkinson(parkinson_vol(quotes, periods_year))
+        }));
+    }
+    if matches!(opts.vol_estimator, VolEstimator::Gk | VolEstimator::All) {
+        tasks.push(Box::new(move || {
+            Indicator::GarmanKlass(garman_klass_vol(quotes, periods_year))
+        }));
+    }
+    // n price changes need n + 1 candles
+    let mfi = opts
+        .mfi
+        .filter(|&period| enough_data(skipped, "MFI", period as usize + 1, quotes.len()));
+    if let Some(period) = mfi {
+        tasks.push(Box::new(move || {
+            Indicator::Mfi(calc_mfi(quotes, period as usize))
+        }));
+    }
+    let cmo = opts
+        .cmo
+        .filter(|&period| enough_data(skipped, "CMO", period as usize + 1, quotes.len()));
+    if let Some(period) = cmo {
+        let price = opts.price_type.unwrap_or(PriceType::Close);
+        tasks.push(Box::new(move || {
+            let period = period as usize;
+            Indicator::Cmo(ChandeMomentum {
+                period,
+                value: calc_cmo(quotes, period, price),
+            })
+        }));
+    }
+    if let Some((ema_period, atr_period, multiplier)) = opts.keltner {
+        let price = opts.price_type.unwrap_or(PriceType::Typical);
+        tasks.push(Box::new(move || {
+            let (ema_period, atr_period) = (ema_period as usize, atr_period as usize);
+            let points = calc_keltner(quotes, price, ema_period, atr_period, multiplier);
+            let latest = points.last();
+            Indicator::Keltner(Keltner {
+                ema_period,
+                atr_period,
+                multiplier,
+                lower: latest.and_then(|p| p.lower),
+                middle: latest.and_then(|p| p.middle),
+                upper: latest.and_then(|p| p.upper),
+                close: quotes.last().map(|q| money_to_f64(&q.close)),
+                points,
+            })
+        }));
+    }
+    if let Some((period, k)) = opts.bollinger {
+        let price = opts.price_type.unwrap_or(PriceType::Close);
+        tasks.push(Box::new(move || {
+            let period = period as usize;
+            Indicator::Bollinger(Bollinger {
+                period,
+                k,
+                points: calc_bollinger(quotes, price, period, k),
+            })
+        }));
+    }
+    if let Some(k) = opts
+        .vwap_bands
+        .filter(|_| opts.quotes.interval.is_intraday())
+    {
+        tasks.push(Box::new(move || {
+            Indicator::VwapBands(calc_vwap_bands(quotes, tz, k))
+        }));
+    }
+    tasks
+}
+
+fn run_tasks(tasks: Vec<Task<'_>>, parallel: bool) -> Vec<Indicator> {
+    if !parallel || tasks.len() < 2 {
+        return tasks.into_iter().map(|task| task()).collect();
+    }
+    std::thread::scope(|s| {
+        let handles: Vec<_> = tasks.into_iter().map(|task| s.spawn(task)).collect();
+        // joined in the order the tasks were added, so results match the sequential path
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("indicator task panicked"))
+            .collect()
+    })
+}
+
+// true with at least `need` candles; otherwise records why `metrics` are missing
+fn enough_data(
+    skipped: &mut Vec<Skipped>,
+    metrics: &'static str,
+    need: usize,
+    have: usize,
+) -> bool {
+    if have < need {
+        skipped.push(Skipped {
+            metrics,
+            need,
+            have,
+        });
+    }
+    have >= need
+}
+
+/// Reads recorded candles for `symbol` from `<dir>/<SYMBOL>.json`, a JSON array of candles in
+/// yfinance-rs' own serialization.
+fn load_fixture(dir: &Path, symbol: &str) -> Result<Vec<Candle>> {
+    let path = dir.join(format!("{}.json", symbol.to_uppercase()));
+    let data = std::fs::read_to_string(&path)
+        .with_context(|| format!("could not read fixture {}", path.display()))?;
+    serde_json::from_str(&data)
+        .with_context(|| format!("could not parse fixture {}", path.display()))
+}
+
+fn calc_coverage(quotes: &[Candle], tz: Option<Tz>, exchange: Exchange) -> Option<Coverage> {
+    let dates: BTreeSet<NaiveDate> = quotes.iter().map(|q| candle_day(q.ts, tz)).collect();
+    let expected = exchange.trading_days(*dates.first()?, *dates.last()?);
+    let missing = expected
+        .iter()
+        .filter(|d| !dates.contains(d))
+        .copied()
+        .collect();
+    Some(Coverage {
+        expected: expected.len(),
+        missing,
+    })
+}
+
+/// The client's retry policy, minus 429s: every Yahoo Finance call backs off for longer in
+/// `retry_rate_limited` instead, see `is_rate_limited`.
+pub fn retry_config() -> RetryConfig {
+    let mut cfg = RetryConfig::default();
+    cfg.retry_on_status.retain(|&status| status != 429);
+    cfg
+}
+
+// yfinance-rs doesn't expose the Retry-After header, so this backs off on a fixed schedule
+async fn retry_rate_limited<T, F, Fut>(fetch: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, YfError>>,
+{
+    backoff_rate_limited(RATE_LIMIT_BACKOFF, fetch).await
+}
+
+// chart and quote calls check the status and return RateLimited, but quoteSummary and the
+// fundamentals timeseries parse whatever body came back, so their 429s ("Too Many Requests")
+// arrive as JSON that doesn't parse at all; JSON of the wrong shape is a real error
+fn is_rate_limited(e: &YfError) -> bool {
+    match e {
+        YfError::RateLimited { .. } => true,
+        YfError::Json(e) => e.is_syntax() || e.is_eof(),
+        _ => false,
+    }
+}
+
+async fn backoff_rate_limited<T, F, Fut>(mut wait: Duration, mut fetch: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, YfError>>,
+{
+    for _ in 0..RATE_LIMIT_RETRIES {
+        match fetch().await {
+            Err(e) if is_rate_limited(&e) => {
+                tokio::time::sleep(wait).await;
+                wait *= 2;
+            }
+            res => return Ok(res?),
+        }
+    }
+    match fetch().await {
+        Err(e) if is_rate_limited(&e) => {
+            anyhow::bail!("rate limited by Yahoo Finance, try again later or lower --concurrency")
+        }
+        res => Ok(res?),
+    }
+}
+
+async fn get_quotes(ticker: &Ticker, opts: &QuoteOptions) -> Result<(Vec<Candle>, Option<Tz>)> {
+    let hist = retry_rate_limited(|| {
+        ticker
+            .history_builder()
+            .range(opts.range)
+            .interval(opts.interval)
+            .auto_adjust(opts.auto_adjust)
+            .prepost(opts.prepost)
+            .actions(opts.include_actions)
+            .fetch_full()
+    })
+    .await?;
+    let tz = hist.meta.and_then(|m| m.timezone);
+    Ok((hist.candles, tz))
+}
+
+/// The exchange-local date a timestamp falls on; extended-hours bars late in the evening are
+/// already on the next day in UTC.
+fn candle_day(ts: DateTime<Utc>, tz: Option<Tz>) -> NaiveDate {
+    ts.with_timezone(&tz.unwrap_or(DEFAULT_TZ)).date_naive()
+}
+
+fn session_of(ts: DateTime<Utc>, tz: Option<Tz>) -> Session {
+    // regular US hours in the exchange's local time; bars are stamped with their start time
+    let local = ts.with_timezone(&tz.unwrap_or(DEFAULT_TZ)).time();
+    if local < NaiveTime::from_hms_opt(9, 30, 0).unwrap() {
+        Session::Pre
+    } else if local >= NaiveTime::from_hms_opt(16, 0, 0).unwrap() {
+        Session::Post
+    } else {
+        Session::Regular
+    }
+}
+
+fn periods_per_year(interval: Interval) -> f64 {
+    match interval {
+        Interval::D5 => TRADING_DAYS_YEAR / 5.0,
+        Interval::W1 => 52.0,
+        Interval::M1 => 12.0,
+        Interval::M3 => 4.0,
+        i => match i.minutes() {
+            Some(m) => TRADING_DAYS_YEAR * TRADING_MINUTES_DAY / m as f64,
+            None => TRADING_DAYS_YEAR,
+        },
+    }
+}
+
+async fn get_earnings_dates(ticker: &Ticker) -> Result<Vec<DateTime<Utc>>> {
+    let cal = retry_rate_limited(|| ticker.calendar()).await?;
+    let earnings = cal.earnings_dates;
+    Ok(earnings)
+}
+
+/// Annualized volatility in percent from the sample std dev of close-to-close returns.
+pub fn close_vol(returns: &[f64], periods_year: f64) -> Option<f64> {
+    (returns.len() >= 2).then(|| returns.std_dev() * periods_year.sqrt() * 100.0)
+}
+
+/// Annualized Parkinson volatility in percent, from each candle's high/low range.
+pub fn parkinson_vol(quotes: &[Candle], periods_year: f64) -> Option<f64> {
+    let ranges: Vec<f64> = quotes
+        .iter()
+        .filter_map(|q| log_ratio(&q.high, &q.low))
+        .collect();
+    if ranges.is_empty() {
+        return None;
+    }
+    let variance = ranges.iter().map(|r| r * r).sum::<f64>()
+        / (4.0 * std::f64::consts::LN_2 * ranges.len() as f64);
+    Some((variance * periods_year).sqrt() * 100.0)
+}
+
+/// Annualized Garman-Klass volatility in percent, from each candle's open, high, low and close.
+pub fn garman_klass_vol(quotes: &[Candle], periods_year: f64) -> Option<f64> {
+    let terms: Vec<f64> = quotes
+        .iter()
+        .filter_map(|q| {
+            let hl = log_ratio(&q.high, &q.low)?;
+            let co = log_ratio(&q.close, &q.open)?;
+            Some(0.5 * hl * hl - (2.0 * std::f64::consts::LN_2 - 1.0) * co * co)
+        })
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    // a strong open-to-close move can make the estimate negative on tiny samples
+    let variance = (terms.iter().sum::<f64>() / terms.len() as f64).max(0.0);
+    Some((variance * periods_year).sqrt() * 100.0)
+}
+
+// ln(a / b), skipping candles with a zero or missing price
+fn log_ratio(a: &Money, b: &Money) -> Option<f64> {
+    let (a, b) = (money_to_f64(a), money_to_f64(b));
+    (a > 0.0 && b > 0.0).then(|| (a / b).ln())
+}
+
+fn calc_returns(quotes: &[Candle]) -> Vec<f64> {
+    let mut res: Vec<f64> = vec![];
+    for i in 1..quotes.len() {
+        let cur = money_to_f64(&quotes[i].close);
+        let prev = money_to_f64(&quotes[i - 1].close);
+        res.push((cur - prev) / prev);
+    }
+    res
+}
+
+fn calc_cagr(quotes: &[Candle], basis: AnnualizationBasis, periods_year: f64) -> Option<f64> {
+    let first = quotes.first()?;
+    let last = quotes.last()?;
+    let start = money_to_f64(&first.close);
+    let end = money_to_f64(&last.close);
+    let years = match basis {
+        AnnualizationBasis::Trading => (quotes.len() - 1) as f64 / periods_year,
+        AnnualizationBasis::Calendar => {
+            (last.ts - first.ts).num_seconds() as f64 / (365.0 * 86400.0)
+        }
+    };
+    if start <= 0.0 || years <= 0.0 {
+        return None;
+    }
+    Some((end / start).powf(1.0 / years) - 1.0)
+}
+
+pub fn calc_drawdowns(quotes: &[Candle]) -> Vec<f64> {
+    let closes: Vec<f64> = quotes.iter().map(|q| money_to_f64(&q.close)).collect();
+    drawdowns(&closes)
+}
+
+fn drawdowns(values: &[f64]) -> Vec<f64> {
+    // percentage below the running peak, one value per value (0 at a new high)
+    let mut peak = f64::NEG_INFINITY;
+    values
+        .iter()
+        .map(|&close| {
+            peak = peak.max(close);
+            if peak > 0.0 {
+                100.0 * (peak - close) / peak
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+fn calc_max_drawdown(quotes: &[Candle]) -> Option<f64> {
+    if quotes.len() < 2 {
+        return None;
+    }
+    calc_drawdowns(quotes).into_iter().reduce(f64::max)
+}
+
+pub fn calc_drawdown_duration(quotes: &[Candle]) -> Option<DrawdownDuration> {
+    if quotes.len() < 2 {
+        return None;
+    }
+    let mut peak = 0;
+    let mut longest: Option<(usize, usize, usize)> = None;
+    for (i, q) in quotes.iter().enumerate() {
+        if money_to_f64(&q.close) >= money_to_f64(&quotes[peak].close) {
+            peak = i;
+        }
+        // candles below the peak so far, ending at this one
+        let below = i - peak;
+        if below > 0 && longest.is_none_or(|(n, _, _)| below > n) {
+            longest = Some((below, peak, i));
+        }
+    }
+    // None for a series that never closed below an earlier peak
+    let (longest, start, last_below) = longest?;
+    let underwater_since = (peak < quotes.len() - 1).then(|| quotes[peak].ts);
+    // the candle after the longest run closed back at the peak, if there is one
+    let recovered = last_below + 1 < quotes.len();
+    let end = if recovered {
+        last_below + 1
+    } else {
+        last_below
+    };
+    Some(DrawdownDuration {
+        longest,
+        start: quotes[start].ts,
+        end: quotes[end].ts,
+        recovered,
+        underwater_since,
+    })
+}
+
+/// Closes of each `(symbol, candles, exchange tz)` series over 100 from their latest common start.
+pub fn calc_rebased(
+    series: &[(String, Vec<Candle>, Option<Tz>)],
+    intraday: bool,
+) -> Option<Rebased> {
+    // daily candles from different exchanges carry different times, so line those up by each
+    // exchange's local date
+    let key = |ts: DateTime<Utc>, tz: Option<Tz>| {
+        if intraday {
+            ts
+        } else {
+            candle_day(ts, tz).and_time(NaiveTime::MIN).and_utc()
+        }
+    };
+    let closes: Vec<BTreeMap<DateTime<Utc>, f64>> = series
+        .iter()
+        .map(|(_, quotes, tz)| {
+            quotes
+                .iter()
+                .map(|q| (key(q.ts, *tz), money_to_f64(&q.close)))
+                .collect()
+        })
+        .collect();
+    let start = *closes
+        .iter()
+        .map(|c| c.keys().next())
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .max()?;
+    // every ticker needs a close on or before the start and some overlap after it
+    let bases = closes
+        .iter()
+        .map(|c| {
+            let base = c.range(..=start).next_back().map(|(_, &v)| v)?;
+            (base > 0.0 && c.keys().next_back()? >= &start).then_some(base)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let mut dates: Vec<DateTime<Utc>> = closes
+        .iter()
+        .flat_map(|c| c.range(start..).map(|(&k, _)| k))
+        .collect();
+    dates.sort();
+    dates.dedup();
+    let rows = dates
+        .into_iter()
+        .map(|date| {
+            let values = closes
+                .iter()
+                .zip(&bases)
+                .map(|(c, base)| c.get(&date).map(|v| 100.0 * v / base))
+                .collect();
+            (date, values)
+        })
+        .collect();
+    Some(Rebased {
+        start,
+        symbols: series.iter().map(|(s, _, _)| s.clone()).collect(),
+        rows,
+    })
+}
+
+/// Simulates a long-only SMA crossover on `price`, the closes unless `--price-type` says
+/// otherwise. The rules, so runs are reproducible:
+/// - buy when the `short` SMA closes above the `long` SMA after being at or below it, and sell
+///   on the reverse cross
+/// - the order fills at the next candle's open, so a cross on the last candle is never filled
+/// - each fill is all-in and pays `commission` as a fraction of its value
+/// - a position still open at the end is marked to the last close without an exit commission
+pub fn calc_backtest(
+    quotes: &[Candle],
+    price: PriceType,
+    short: usize,
+    long: usize,
+    commission: f64,
+) -> Backtest {
+    let closes: Vec<f64> = quotes.iter().map(|q| money_to_f64(&q.close)).collect();
+    // the signal follows `price`; equity is still marked to the closes
+    let prices: Vec<f64> = quotes.iter().map(|q| price.of(q)).collect();
+    let sma = |period: usize| {
+        let means = rolling_mean(&prices, period);
+        move |i: usize| {
+            (i + 1)
+                .checked_sub(period)
+                .and_then(|j| means.get(j))
+                .copied()
+        }
+    };
+    let (short_sma, long_sma) = (sma(short), sma(long));
+    let above = |i: usize| match (short_sma(i), long_sma(i)) {
+        (Some(s), Some(l)) => Some(s > l),
+        _ => None,
+    };
+
+    let mut cash = 1.0;
+    let mut shares = 0.0;
+    // the entry candle, price and cash spent for the open position
+    let mut open: Option<(DateTime<Utc>, f64, f64)> = None;
+    // true to buy, false to sell at the next open
+    let mut pending: Option<bool> = None;
+    let mut trades = vec![];
+    let mut equity = vec![];
+    for (i, q) in quotes.iter().enumerate() {
+        let price = money_to_f64(&q.open);
+        match pending.take() {
+            Some(true) => {
+                open = Some((q.ts, price, cash));
+                shares = cash * (1.0 - commission) / price;
+                cash = 0.0;
+            }
+            Some(false) => {
+                cash = shares * price * (1.0 - commission);
+                shares = 0.0;
+                if let Some((entry, entry_price, spent)) = open.take() {
+                    trades.push(Trade {
+                        entry,
+                        entry_price,
+                        exit: Some(q.ts),
+                        exit_price: price,
+                        return_pct: 100.0 * (cash / spent - 1.0),
+                    });
+                }
+            }
+            None => {}
+        }
+        let value = cash + shares * closes[i];
+        equity.push(SeriesPoint {
+            ts: q.ts,
+            value: Some(value),
+        });
+        if let (Some(was), Some(now)) = (i.checked_sub(1).and_then(above), above(i)) {
+            if now != was && now == open.is_none() {
+                pending = Some(now);
+            }
+        }
+    }
+    let last = equity.last().and_then(|p| p.value).unwrap_or(1.0);
+    if let (Some((entry, entry_price, spent)), Some(q)) = (open, quotes.last()) {
+        trades.push(Trade {
+            entry,
+            entry_price,
+            exit: None,
+            exit_price: money_to_f64(&q.close),
+            return_pct: 100.0 * (last / spent - 1.0),
+        });
+    }
+
+    let values: Vec<f64> = equity.iter().filter_map(|p| p.value).collect();
+    let wins = trades.iter().filter(|t| t.return_pct > 0.0).count();
+    let (first, final_close) = (closes.first(), closes.last());
+    Backtest {
+        short,
+        long,
+        commission,
+        total_return: 100.0 * (last - 1.0),
+        win_rate: (!trades.is_empty()).then(|| 100.0 * wins as f64 / trades.len() as f64),
+        trades,
+        max_drawdown: drawdowns(&values).into_iter().fold(0.0, f64::max),
+        hold_return: match (first, final_close) {
+            (Some(&f), Some(&l)) if f > 0.0 => 100.0 * (l / f - 1.0),
+            _ => 0.0,
+        },
+        hold_max_drawdown: drawdowns(&closes).into_iter().fold(0.0, f64::max),
+        equity,
+    }
+}
+
+fn calc_ulcer_index(quotes: &[Candle]) -> Option<f64> {
+    // root-mean-square of the drawdowns, so both depth and duration count
+    if quotes.len() < 2 {
+        return None;
+    }
+    let drawdowns = calc_drawdowns(quotes);
+    let mean_sq = drawdowns.iter().map(|d| d * d).sum::<f64>() / drawdowns.len() as f64;
+    Some(mean_sq.sqrt())
+}
+
+fn calc_earnings_history(earnings: &Earnings) -> EarningsHistory {
+    let quarters = earnings
+        .quarterly_eps
+        .iter()
+        .map(|q| {
+            let reported = q.actual.as_ref().map(money_to_f64);
+            let estimate = q.estimate.as_ref().map(money_to_f64);
+            let surprise_pct = match (reported, estimate) {
+                (Some(r), Some(e)) if e != 0.0 => Some(100.0 * (r - e) / e.abs()),
+                _ => None,
+            };
+            EpsSurprise {
+                period: q.period.to_string(),
+                reported,
+                estimate,
+                surprise_pct,
+            }
+        })
+        .collect();
+    let years = earnings
+        .yearly
+        .iter()
+        .map(|y| AnnualEarnings {
+            year: y.year,
+            revenue: y.revenue.as_ref().map(money_to_f64),
+            earnings: y.earnings.as_ref().map(money_to_f64),
+        })
+        .collect();
+    EarningsHistory { quarters, years }
+}
+
+/// Index of the candle an earnings date falls on; a weekend or holiday date snaps forward to the
+/// next trading day. Both sides are compared in exchange time. `None` when the date is outside
+/// the window.
+pub fn earnings_candle(
+    quotes: &[Candle],
+    tz: Option<Tz>,
+    earnings: &DateTime<Utc>,
+) -> Option<usize> {
+    let date = candle_day(*earnings, tz);
+    let idx = quotes.iter().position(|q| candle_day(q.ts, tz) >= date)?;
+    // a date before the window would otherwise snap to the first candle
+    (idx > 0 || candle_day(quotes[0].ts, tz) == date).then_some(idx)
+}
+
+fn calc_earnings_impact(
+    quotes: &[Candle],
+    tz: Option<Tz>,
+    earnings: &[DateTime<Utc>],
+) -> EarningsImpact {
+    let close = |i: usize| money_to_f64(&quotes[i].close);
+    let ret = |i: usize| (i > 0 && i < quotes.len()).then(|| close(i) / close(i - 1) - 1.0);
+
+    let events: Vec<EarningsEvent> = earnings
+        .iter()
+        .filter_map(|er| {
+            let idx = earnings_candle(quotes, tz, er)?;
+            // a report after the close on a weekend or holiday still moves the next session
+            let after_close = session_of(*er, tz) == Session::Post
+                && candle_day(quotes[idx].ts, tz) == candle_day(*er, tz);
+            Some(EarningsEvent {
+                earnings: *er,
+                trading_day: quotes[idx].ts,
+                day_return: ret(idx),
+                next_day_return: ret(idx + 1),
+                reaction: if after_close {
+                    Reaction::NextDay
+                } else {
+                    Reaction::Day
+                },
+            })
+        })
+        .collect();
+
+    let moves: Vec<f64> = events
+        .iter()
+        .filter_map(|e| match e.reaction {
+            Reaction::Day => e.day_return,
+            Reaction::NextDay => e.next_day_return,
+        })
+        .collect();
+    EarningsImpact {
+        avg_abs_move: (!moves.is_empty())
+            .then(|| moves.iter().map(|m| m.abs()).sum::<f64>() / moves.len() as f64),
+        up: moves.iter().filter(|m| **m > 0.0).count(),
+        down: moves.iter().filter(|m| **m < 0.0).count(),
+        events,
+    }
+}
+
+/// P&L of the latest close against `cost_basis`, and the return since the candle closest to
+/// `buy_date` when one is given.
+fn calc_position(
+    quotes: &[Candle],
+    tz: Option<Tz>,
+    cost_basis: f64,
+    shares: Option<f64>,
+    buy_date: Option<NaiveDate>,
+) -> Option<Position> {
+    let last = money_to_f64(&quotes.last()?.close);
+    let gain = last - cost_basis;
+    let day = |q: &Candle| candle_day(q.ts, tz);
+    // the first candle on the buy date or the next trading day; the last one if it's later still
+    let buy = buy_date.and_then(|date| quotes.iter().find(|q| day(q) >= date).or(quotes.last()));
+    let since_buy = buy.and_then(|q| {
+        let close = money_to_f64(&q.close);
+        (close != 0.0).then(|| 100.0 * (last - close) / close)
+    });
+    Some(Position {
+        cost_basis,
+        shares,
+        pnl_pct: 100.0 * gain / cost_basis,
+        pnl: gain * shares.unwrap_or(1.0),
+        buy_date,
+        buy_candle: buy.map(|q| q.ts),
+        buy_candle_date: buy.map(day),
+        since_buy,
+    })
+}
+
+/// Groups returns by the weekday (0 = Monday) or month (0 = January) of the candle they end on,
+/// in the exchange's local time.
+fn weekday_returns(quotes: &[Candle], returns: &[f64], tz: Option<Tz>) -> BTreeMap<u32, Vec<f64>> {
+    let tz = tz.unwrap_or(DEFAULT_TZ);
+    let mut buckets: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+    // returns[i] is the move into quotes[i + 1]
+    for (q, r) in quotes.iter().skip(1).zip(returns) {
+        let key = q.ts.with_timezone(&tz).weekday().num_days_from_monday();
+        buckets.entry(key).or_default().push(*r);
+    }
+    buckets
+}
+
+/// Return of each calendar month from the last close of the month before, keyed by year and
+/// month0. The first month and any month after a gap have nothing to compare with and are left
+/// out; the latest month is month to date.
+fn monthly_returns(quotes: &[Candle], tz: Option<Tz>) -> BTreeMap<(i32, u32), f64> {
+    let tz = tz.unwrap_or(DEFAULT_TZ);
+    let mut month_end: BTreeMap<(i32, u32), f64> = BTreeMap::new();
+    for q in quotes {
+        let local = q.ts.with_timezone(&tz);
+        month_end.insert((local.year(), local.month0()), money_to_f64(&q.close));
+    }
+    month_end
+        .iter()
+        .filter_map(|(&(year, month0), &close)| {
+            let prev = match month0 {
+                0 => (year - 1, 11),
+                _ => (year, month0 - 1),
+            };
+            let base = *month_end.get(&prev)?;
+            (base != 0.0).then(|| ((year, month0), close / base - 1.0))
+        })
+        .collect()
+}
+
+fn calc_seasonality(
+    quotes: &[Candle],
+    returns: &[f64],
+    tz: Option<Tz>,
+    basis: SeasonalityBasis,
+) -> Seasonality {
+    const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    let (buckets, years) = match basis {
+        SeasonalityBasis::Weekday => (weekday_returns(quotes, returns, tz), vec![]),
+        SeasonalityBasis::Month => {
+            let monthly = monthly_returns(quotes, tz);
+            let mut years: BTreeMap<i32, [Option<f64>; 12]> = BTreeMap::new();
+            let mut buckets: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+            for (&(year, month0), &r) in &monthly {
+                years.entry(year).or_default()[month0 as usize] = Some(r);
+                buckets.entry(month0).or_default().push(r);
+            }
+            let years = years
+                .into_iter()
+                .map(|(year, months)| SeasonYear { year, months })
+                .collect();
+            (buckets, years)
+        }
+    };
+    let buckets = buckets
+        .into_iter()
+        .map(|(key, rets)| {
+            let label = match basis {
+                SeasonalityBasis::Weekday => WEEKDAYS[key as usize],
+                SeasonalityBasis::Month => MONTHS[key as usize],
+            };
+            let enough = rets.len() >= MIN_SEASON_SAMPLES;
+            let wins = rets.iter().filter(|r| **r > 0.0).count();
+            SeasonBucket {
+                label: label.to_string(),
+                count: rets.len(),
+                avg_return: enough.then(|| rets.iter().sum::<f64>() / rets.len() as f64),
+                win_rate: enough.then(|| wins as f64 / rets.len() as f64),
+            }
+        })
+        .collect();
+    Seasonality {
+        basis,
+        buckets,
+        years,
+    }
+}
+
+fn typical_price(q: &Candle) -> f64 {
+    PriceType::Typical.of(q)
+}
+
+/// Keltner channel at each candle: an EMA of `price` with bands `multiplier` ATRs either side.
+fn calc_keltner(
+    quotes: &[Candle],
+    price: PriceType,
+    ema_period: usize,
+    atr_period: usize,
+    multiplier: f64,
+) -> Vec<BandPoint> {
+    let prices: Vec<f64> = quotes.iter().map(|q| price.of(q)).collect();
+    let ema = ema(&prices, ema_period);
+    let atr = atr(quotes, atr_period);
+    quotes
+        .iter()
+        .enumerate()
+        .map(|(i, q)| {
+            // ema[0] lines up with the end of the first period, atr[0] one candle later
+            let middle = (i + 1)
+                .checked_sub(ema_period)
+                .and_then(|j| ema.get(j))
+                .copied();
+            let range = i
+                .checked_sub(atr_period)
+                .and_then(|j| atr.get(j))
+                .map(|a| a * multiplier);
+            let (lower, upper) = match (middle, range) {
+                (Some(m), Some(r)) => (Some(m - r), Some(m + r)),
+                _ => (None, None),
+            };
+            BandPoint {
+                ts: q.ts,
+                lower,
+                middle,
+                upper,
+            }
+        })
+        .collect()
+}
+
+/// Bollinger bands at each candle: the `period` mean of `price` with bands `k` sample standard
+/// deviations either side.
+fn calc_bollinger(quotes: &[Candle], price: PriceType, period: usize, k: f64) -> Vec<BandPoint> {
+    let prices: Vec<f64> = quotes.iter().map(|q| price.of(q)).collect();
+    let means = rolling_mean(&prices, period);
+    let stds = rolling_std(&prices, period);
+    quotes
+        .iter()
+        .enumerate()
+        .map(|(i, q)| {
+            let j = (i + 1).checked_sub(period);
+            let middle = j.and_then(|j| means.get(j)).copied();
+            let std = j.and_then(|j| stds.get(j)).copied();
+            let (lower, upper) = match (middle, std) {
+                (Some(m), Some(s)) => (Some(m - k * s), Some(m + k * s)),
+                _ => (None, None),
+            };
+            BandPoint {
+                ts: q.ts,
+                lower,
+                middle,
+                upper,
+            }
+        })
+        .collect()
+}
+
+fn calc_squeeze(keltner: &[BandPoint], bollinger: &[BandPoint]) -> Squeeze {
+    // the usual squeeze: Bollinger bands contract inside the Keltner channel as volatility compresses
+    let inside: Vec<bool> = keltner
+        .iter()
+        .zip(bollinger)
+        .map(|(kc, bb)| match (kc.lower, kc.upper, bb.lower, bb.upper) {
+            (Some(kl), Some(ku), Some(bl), Some(bu)) => bl > kl && bu < ku,
+            _ => false,
+        })
+        .collect();
+    Squeeze {
+        points: keltner
+            .iter()
+            .zip(&inside)
+            .filter(|(_, inside)| **inside)
+            .map(|(p, _)| p.ts)
+            .collect(),
+        active: inside.last().copied().unwrap_or(false),
+    }
+}
+
+/// Volume-weighted average of the typical price and its volume-weighted standard deviation,
+/// accumulated from the start of each local trading day.
+fn calc_vwap(quotes: &[Candle], tz: Option<Tz>) -> Vec<Option<(f64, f64)>> {
+    let mut day = None;
+    running_vwap(quotes, |q| {
+        let date = candle_day(q.ts, tz);
+        let new_day = day != Some(date);
+        day = Some(date);
+        new_day
+    })
+}
+
+/// VWAP at each candle from `quotes[anchor]` on, without resetting between sessions; `None`
+/// until a candle with volume.
+pub fn calc_anchored_vwap(quotes: &[Candle], anchor: usize) -> Vec<Option<f64>> {
+    let quotes = quotes.get(anchor..).unwrap_or_default();
+    running_vwap(quotes, |_| false)
+        .into_iter()
+        .map(|p| p.map(|(vwap, _)| vwap))
+        .collect()
+}
+
+// running VWAP of the typical price and its volume-weighted std dev, starting over wherever
+// `reset` is true; candles without volume add nothing
+fn running_vwap(
+    quotes: &[Candle],
+    mut reset: impl FnMut(&Candle) -> bool,
+) -> Vec<Option<(f64, f64)>> {
+    let (mut volume, mut pv, mut pv2) = (0.0, 0.0, 0.0);
+    quotes
+        .iter()
+        .map(|q| {
+            if reset(q) {
+                (volume, pv, pv2) = (0.0, 0.0, 0.0);
+            }
+            if let Some(v) = q.volume {
+                let tp = typical_price(q);
+                volume += v as f64;
+                pv += tp * v as f64;
+                pv2 += tp * tp * v as f64;
+            }
+            if volume == 0.0 {
+                return None;
+            }
+            let vwap = pv / volume;
+            // rounding can push the variance slightly below zero
+            let variance = (pv2 / volume - vwap * vwap).max(0.0);
+            Some((vwap, variance.sqrt()))
+        })
+        .collect()
+}
+
+fn calc_vwap_bands(quotes: &[Candle], tz: Option<Tz>, k: f64) -> VwapBands {
+    let mut bands = VwapBands {
+        k,
+        points: vec![],
+        above: 0,
+        below: 0,
+    };
+    for (q, vwap) in quotes.iter().zip(calc_vwap(quotes, tz)) {
+        let (upper, lower) = match vwap {
+            Some((vwap, std)) => (Some(vwap + k * std), Some(vwap - k * std)),
+            None => (None, None),
+        };
+        let close = money_to_f64(&q.close);
+        if upper.is_some_and(|u| close > u) {
+            bands.above += 1;
+        } else if lower.is_some_and(|l| close < l) {
+            bands.below += 1;
+        }
+        bands.points.push(VwapPoint {
+            ts: q.ts,
+            vwap: vwap.map(|(vwap, _)| vwap),
+            upper,
+            lower,
+        });
+    }
+    bands
+}
+
+/// Chande Momentum Oscillator over the last `period` changes in `price`, from -100 to 100; 0
+/// when the price didn't move.
+pub fn calc_cmo(quotes: &[Candle], period: usize, price: PriceType) -> Option<f64> {
+    if period == 0 || quotes.len() < period + 1 {
+        return None;
+    }
+    let prices: Vec<f64> = quotes[quotes.len() - period - 1..]
+        .iter()
+        .map(|q| price.of(q))
+        .collect();
+    let (up, down) = prices.windows(2).fold((0.0, 0.0), |(up, down), w| {
+        let change = w[1] - w[0];
+        if change > 0.0 {
+            (up + change, down)
+        } else {
+            (up, down - change)
+        }
+    });
+    if up + down == 0.0 {
+        return Some(0.0);
+    }
+    Some(100.0 * (up - down) / (up + down))
+}
+
+/// Money Flow Index over the last `period` typical-price changes, from 0 to 100; 50 when the
+/// price didn't move.
+pub fn calc_mfi(quotes: &[Candle], period: usize) -> MoneyFlowIndex {
+    // money flow needs volume, so candles without it are dropped (and counted) first
+    let flows: Vec<(f64, f64)> = quotes
+        .iter()
+        .filter_map(|q| Some((typical_price(q), q.volume? as f64)))
+        .collect();
+    let skipped = quotes.len() - flows.len();
+    let mut mfi = MoneyFlowIndex {
+        period,
+        value: None,
+        skipped,
+    };
+    if flows.len() < period + 1 {
+        return mfi;
+    }
+
+    let (mut positive, mut negative) = (0.0, 0.0);
+    for w in flows[flows.len() - period - 1..].windows(2) {
+        let (prev_tp, _) = w[0];
+        let (tp, volume) = w[1];
+        if tp > prev_tp {
+            positive += tp * volume;
+        } else if tp < prev_tp {
+            negative += tp * volume;
+        }
+    }
+    mfi.value = Some(if negative == 0.0 {
+        // a flat market sits at the midpoint; nothing but inflows pins it at 100
+        if positive == 0.0 {
+            50.0
+        } else {
+            100.0
+        }
+    } else {
+        100.0 - 100.0 / (1.0 + positive / negative)
+    });
+    mfi
+}
+
+/// Volume of the latest candle over the average volume of the `window` candles before it; the
+/// ones without volume are left out of the average and counted.
+pub fn calc_relative_volume(quotes: &[Candle], window: usize) -> RelativeVolume {
+    let mut rv = RelativeVolume {
+        window,
+        value: None,
+        skipped: 0,
+    };
+    let Some((last, rest)) = quotes.split_last() else {
+        return rv;
+    };
+    if window == 0 || rest.len() < window {
+        return rv;
+    }
+    let prior: Vec<f64> = rest[rest.len() - window..]
+        .iter()
+        .filter_map(|q| q.volume.map(|v| v as f64))
+        .collect();
+    rv.skipped = window - prior.len();
+    if let (Some(latest), false) = (last.volume, prior.is_empty()) {
+        let avg = prior.iter().sum::<f64>() / prior.len() as f64;
+        rv.value = (avg > 0.0).then(|| latest as f64 / avg);
+    }
+    rv
+}
+
+/// Average and median of close times volume; `None` when no candle has volume.
+pub fn calc_dollar_volume(quotes: &[Candle]) -> Option<DollarVolume> {
+    let mut values: Vec<f64> = quotes
+        .iter()
+        .filter_map(|q| q.volume.map(|v| money_to_f64(&q.close) * v as f64))
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let n = values.len();
+    let median = if n.is_multiple_of(2) {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    } else {
+        values[n / 2]
+    };
+    Some(DollarVolume {
+        average: values.iter().sum::<f64>() / n as f64,
+        median,
+        skipped: quotes.len() - n,
+    })
+}
+
+fn get_price_range(quotes: &[Candle]) -> Option<(PriceRange, PriceRange)> {
+    // get intraday and closing price ranges over time period
+    if quotes.is_empty() {
+        return None;
+    }
+
+    let mut intraday = PriceRange {
+        low: f64::INFINITY,
+        high: f64::NEG_INFINITY,
+    };
+    let mut closing = PriceRange {
+        low: f64::INFINITY,
+        high: f64::NEG_INFINITY,
+    };
+    for q in quotes {
+        let low = money_to_f64(&q.low);
+        let high = money_to_f64(&q.high);
+        let close = money_to_f64(&q.close);
+        intraday.low = intraday.low.min(low);
+        intraday.high = intraday.high.max(high);
+        closing.low = closing.low.min(close);
+        closing.high = closing.high.max(close);
+    }
+
+    Some((intraday, closing))
+}
+
+fn aligned_returns(
+    quotes: &[Candle],
+    tz: Option<Tz>,
+    bench: &[Candle],
+    bench_tz: Option<Tz>,
+    interval: Interval,
+) -> Vec<(DateTime<Utc>, f64, f64)> {
+    // pair up closes on matching local dates (or bar times for intraday), then take returns
+    // between consecutive matched pairs so gaps in either series don't skew the result
+    let key = |c: &Candle, tz: Option<Tz>| {
+        if interval.is_intraday() {
+            c.ts.timestamp()
+        } else {
+            candle_day(c.ts, tz)
+                .and_time(NaiveTime::MIN)
+                .and_utc()
+                .timestamp()
+        }
+    };
+    let bench_closes: HashMap<i64, f64> = bench
+        .iter()
+        .map(|c| (key(c, bench_tz), money_to_f64(&c.close)))
+        .collect();
+    let closes: Vec<(DateTime<Utc>, f64, f64)> = quotes
+        .iter()
+        .filter_map(|c| {
+            let b = bench_closes.get(&key(c, tz))?;
+            Some((c.ts, money_to_f64(&c.close), *b))
+        })
+        .collect();
+
+    closes
+        .windows(2)
+        .map(|w| {
+            let (_, prev_x, prev_y) = w[0];
+            let (ts, cur_x, cur_y) = w[1];
+            (ts, (cur_x - prev_x) / prev_x, (cur_y - prev_y) / prev_y)
+        })
+        .collect()
+}
+
+fn correlation(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    // Pearson correlation; undefined when either series has no variance
+    if xs.len() != ys.len() || xs.len() < 2 {
+        return None;
+    }
+    let mean_x = xs.mean();
+    let mean_y = ys.mean();
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x < f64::EPSILON || var_y < f64::EPSILON {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+fn beta(xs: &[f64], bench: &[f64]) -> Option<f64> {
+    // slope of the least-squares fit of the ticker's returns on the benchmark's
+    if xs.len() != bench.len() || xs.len() < 2 {
+        return None;
+    }
+    let mean_x = xs.mean();
+    let mean_b = bench.mean();
+    let (mut cov, mut var_b) = (0.0, 0.0);
+    for (x, b) in xs.iter().zip(bench) {
+        cov += (x - mean_x) * (b - mean_b);
+        var_b += (b - mean_b).powi(2);
+    }
+    if var_b < f64::EPSILON {
+        return None;
+    }
+    Some(cov / var_b)
+}
+
+pub type ReturnSeries = (Vec<f64>, Vec<f64>);
+
+fn split_by_benchmark(pairs: &[(DateTime<Utc>, f64, f64)]) -> (ReturnSeries, ReturnSeries) {
+    // (ticker, benchmark) returns on benchmark-up days and on benchmark-down days
+    let mut up = (vec![], vec![]);
+    let mut down = (vec![], vec![]);
+    for (_, x, b) in pairs {
+        if *b > 0.0 {
+            up.0.push(*x);
+            up.1.push(*b);
+        } else if *b < 0.0 {
+            down.0.push(*x);
+            down.1.push(*b);
+        }
+    }
+    (up, down)
+}
+
+fn capture_ratio((xs, bench): &ReturnSeries) -> Option<f64> {
+    // ticker's mean return over the benchmark's mean return on the same days, as a percentage
+    if xs.is_empty() {
+        return None;
+    }
+    let bench_mean = bench.mean();
+    if bench_mean.abs() < f64::EPSILON {
+        return None;
+    }
+    Some(100.0 * xs.mean() / bench_mean)
+}
+
+fn rolling_correlation(pairs: &[(DateTime<Utc>, f64, f64)], window: usize) -> Vec<SeriesPoint> {
+    if window == 0 || pairs.len() < window {
+        return vec![];
+    }
+    pairs
+        .windows(window)
+        .map(|w| {
+            let (xs, ys): (Vec<f64>, Vec<f64>) = w.iter().map(|(_, x, y)| (*x, *y)).unzip();
+            SeriesPoint {
+                ts: w[window - 1].0,
+                value: correlation(&xs, &ys),
+            }
+        })
+        .collect()
+}
+
+async fn get_risk_free_rate(client: &YfClient, symbol: &str) -> Result<f64> {
+    // yield tickers such as ^IRX (13 week treasury bill) quote the annual rate in percent
+    let ticker = Ticker::new(client, symbol);
+    let fi = retry_rate_limited(|| ticker.fast_info()).await?;
+    let last = fi
+        .last
+        .ok_or_else(|| anyhow::anyhow!("Could not retrieve {} price", symbol))?;
+    let rate = money_to_f64(&last) / 100.0;
+    Ok(rate)
+}
+
+fn rolling_mean(values: &[f64], window: usize) -> Vec<f64> {
+    // one value per full window, aligned with the window's last element
+    if window == 0 {
+        return vec![];
+    }
+    values.windows(window).map(|w| w.mean()).collect()
+}
+
+fn ema(values: &[f64], period: usize) -> Vec<f64> {
+    // seeded with the mean of the first period, then aligned with each later value
+    if period == 0 || values.len() < period {
+        return vec![];
+    }
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut res = vec![values[..period].mean()];
+    for v in &values[period..] {
+        let prev = res[res.len() - 1];
+        res.push(prev + alpha * (v - prev));
+    }
+    res
+}
+
+fn atr(quotes: &[Candle], period: usize) -> Vec<f64> {
+    // Wilder's average of the true range; the first candle has no previous close
+    let true_ranges: Vec<f64> = quotes
+        .windows(2)
+        .map(|w| {
+            let prev_close = money_to_f64(&w[0].close);
+            let (high, low) = (money_to_f64(&w[1].high), money_to_f64(&w[1].low));
+            (high - low)
+                .max((high - prev_close).abs())
+                .max((low - prev_close).abs())
+        })
+        .collect();
+    if period == 0 || true_ranges.len() < period {
+        return vec![];
+    }
+    let mut res = vec![true_ranges[..period].mean()];
+    for tr in &true_ranges[period..] {
+        let prev = res[res.len() - 1];
+        res.push((prev * (period - 1) as f64 + tr) / period as f64);
+    }
+    res
+}
+
+fn rolling_std(values: &[f64], window: usize) -> Vec<f64> {
+    // sample standard deviation of each full window
+    if window < 2 {
+        return vec![];
+    }
+    values.windows(window).map(|w| w.std_dev()).collect()
+}
+
+fn rolling_sharpe(
+    returns: &[f64],
+    window: usize,
+    risk_free_annual: f64,
+    periods_year: f64,
+) -> Vec<Option<f64>> {
+    let risk_free_period = (1.0 + risk_free_annual).powf(1.0 / periods_year) - 1.0;
+    rolling_mean(returns, window)
+        .into_iter()
+        .zip(rolling_std(returns, window))
+        .map(|(mean, std)| {
+            if std < f64::EPSILON {
+                return None;
+            }
+            Some((mean - risk_free_period) / std * periods_year.sqrt())
+        })
+        .collect()
+}
+
+fn omega(returns: &[f64], threshold: f64) -> Option<f64> {
+    // probability-weighted gains above the threshold over the losses below it
+    let gains: f64 = returns.iter().map(|r| (r - threshold).max(0.0)).sum();
+    let losses: f64 = returns.iter().map(|r| (threshold - r).max(0.0)).sum();
+    if losses == 0.0 {
+        // undefined when every return sits on the threshold
+        return (gains > 0.0).then_some(f64::INFINITY);
+    }
+    Some(gains / losses)
+}
+
+fn sortino_ratio(returns: &[f64], risk_free_annual: f64, periods_year: f64) -> f64 {
+    if returns.is_empty() {
+        return 0.0;
+    }
+
+    let risk_free_period = (1.0 + risk_free_annual).powf(1.0 / periods_year) - 1.0;
+    let excess_returns: Vec<f64> = returns.iter().map(|r| r - risk_free_period).collect();
+    let mean_excess = excess_returns.as_slice().mean();
+    let downside_variance = excess_returns
+        .iter()
+        .map(|r| if *r < 0.0 { r * r } else { 0.0 })
+        .sum::<f64>()
+        / excess_returns.len() as f64;
+
+    let downside_std_dev = downside_variance.sqrt();
+    if downside_std_dev < f64::EPSILON {
+        return 0.0;
+    }
+
+    let annualization_factor = periods_year.sqrt();
+    (mean_excess * periods_year) / (downside_std_dev * annualization_factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::cell::Cell;
+
+    fn rate_limited() -> YfError {
+        YfError::RateLimited {
+            url: "https://query1.finance.yahoo.com".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limits() {
+        // two 429s, then an answer
+        let calls = Cell::new(0);
+        let fetch = || {
+            calls.set(calls.get() + 1);
+            let n = calls.get();
+            async move {
+                if n < 3 {
+                    Err(rate_limited())
+                } else {
+                    Ok(n)
+                }
+            }
+        };
+        assert_eq!(
+            backoff_rate_limited(Duration::ZERO, fetch).await.unwrap(),
+            3
+        );
+
+        // every attempt rate limited
+        calls.set(0);
+        let fetch = || {
+            calls.set(calls.get() + 1);
+            async { Err::<(), _>(rate_limited()) }
+        };
+        let err = backoff_rate_limited(Duration::ZERO, fetch)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("rate limited by Yahoo Finance"));
+        assert_eq!(calls.get(), RATE_LIMIT_RETRIES + 1);
+
+        // other errors aren't retried here
+        calls.set(0);
+        let fetch = || {
+            calls.set(calls.get() + 1);
+            async { Err::<(), _>(YfError::Api("bad symbol".to_string())) }
+        };
+        assert!(backoff_rate_limited(Duration::ZERO, fetch).await.is_err());
+        assert_eq!(calls.get(), 1);
+        assert!(!retry_config().retry_on_status.contains(&429));
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limits_that_fail_to_parse() {
+        // a 429 on quoteSummary comes back as its plain-text body failing to parse
+        let json_error = |body: &str| YfError::Json(serde_json::from_str::<i32>(body).unwrap_err());
+        let calls = Cell::new(0);
+        let fetch = || {
+            calls.set(calls.get() + 1);
+            let n = calls.get();
+            async move {
+                match n {
+                    1 => Err(json_error("Too Many Requests")),
+                    2 => Err(json_error("")),
+                    _ => Ok(n),
+                }
+            }
+        };
+        assert_eq!(
+            backoff_rate_limited(Duration::ZERO, fetch).await.unwrap(),
+            3
+        );
+
+        // JSON of the wrong shape fails straight away
+        calls.set(0);
+        let fetch = || {
+            calls.set(calls.get() + 1);
+            async { Err::<(), _>(json_error("\"not a number\"")) }
+        };
+        assert!(backoff_rate_limited(Duration::ZERO, fetch).await.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn earnings_reaction_follows_the_report_time() {
+        // closes from Tue 2nd to Fri 5th, then Mon 8th
+        let money = |v: f64| serde_json::json!({ "amount": v.to_string(), "currency": "USD" });
+        let quotes: Vec<Candle> = [(2, 100.0), (3, 110.0), (4, 99.0), (5, 99.0), (8, 108.9)]
+            .iter()
+            .map(|&(d, c)| {
+                serde_json::from_value(serde_json::json!({
+                    "ts": 1704205800 + (d - 2) * 86400,
+                    "open": money(c), "high": money(c), "low": money(c), "close": money(c),
+                }))
+                .unwrap()
+            })
+            .collect();
+        let at = |d, h, m| {
+            DEFAULT_TZ
+                .with_ymd_and_hms(2024, 1, d, h, m, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        // before the open on the 3rd: the +10% that day; after the close on the 3rd: the -10%
+        // on the 4th; after the "close" on Saturday the 6th: the +10% on Monday
+        let earnings = [at(3, 7, 0), at(3, 16, 30), at(6, 16, 30)];
+        let impact = calc_earnings_impact(&quotes, None, &earnings);
+        let reactions: Vec<Reaction> = impact.events.iter().map(|e| e.reaction).collect();
+        assert_eq!(reactions, [Reaction::Day, Reaction::NextDay, Reaction::Day]);
+        assert!((impact.avg_abs_move.unwrap() - 0.1).abs() < 1e-12);
+        assert_eq!((impact.up, impact.down), (2, 1));
+    }
+
+    #[test]
+    fn omega_of_crafted_returns() {
+        let returns = [0.02, -0.01, 0.03, -0.02];
+        // gains 0.05 over losses 0.03
+        assert!((omega(&returns, 0.0).unwrap() - 5.0 / 3.0).abs() < 1e-12);
+        // measured from 1%: gains 0.01 + 0.02 over losses 0.02 + 0.03
+        assert!((omega(&returns, 0.01).unwrap() - 0.6).abs() < 1e-12);
+        // nothing below the threshold to divide by
+        assert_eq!(omega(&[0.01, 0.02, 0.0], 0.0), Some(f64::INFINITY));
+        // nor above it: 0 / 0
+        assert_eq!(omega(&[0.01, 0.01, 0.01], 0.01), None);
+    }
+
+    #[test]
+    fn rolling_correlation_of_known_pairs() {
+        let ts = |i: i64| DateTime::from_timestamp(1704205800 + i * 86400, 0).unwrap();
+        let xs = [0.01, -0.02, 0.015, 0.03, -0.01, 0.005];
+        let pairs = |f: fn(f64) -> f64| -> Vec<_> {
+            xs.iter()
+                .enumerate()
+                .map(|(i, &x)| (ts(i as i64), x, f(x)))
+                .collect()
+        };
+        let values = |points: Vec<SeriesPoint>| -> Vec<_> {
+            points
+                .iter()
+                .map(|p| (p.value.unwrap() * 1e9).round() / 1e9)
+                .collect()
+        };
+
+        let same = rolling_correlation(&pairs(|x| x), 3);
+        // one point per full window, stamped with the window's last candle
+        assert_eq!(same.len(), 4);
+        assert_eq!((same[0].ts, same[3].ts), (ts(2), ts(5)));
+        assert_eq!(values(same), [1.0; 4]);
+        // scaling and shifting don't change it; negating flips the sign
+        assert_eq!(
+            values(rolling_correlation(&pairs(|x| 2.0 * x + 1.0), 3)),
+            [1.0; 4]
+        );
+        assert_eq!(values(rolling_correlation(&pairs(|x| -x), 3)), [-1.0; 4]);
+
+        // a window as long as the series gives one point, a longer one none
+        assert_eq!(rolling_correlation(&pairs(|x| x), 6).len(), 1);
+        assert!(rolling_correlation(&pairs(|x| x), 7).is_empty());
+        assert!(rolling_correlation(&pairs(|x| x), 0).is_empty());
+        // a flat series has no correlation
+        let flat = rolling_correlation(&pairs(|_| 0.01), 3);
+        assert!(flat.iter().all(|p| p.value.is_none()));
+    }
+}
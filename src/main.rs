@@ -1,20 +1,40 @@
-use anyhow::Result;
+mod catalog;
+mod explain;
+
+use anyhow::{Context, Result};
 use chrono::DateTime;
-use chrono::Utc;
-use clap::{Parser, ValueEnum};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use clap::{CommandFactory, Parser, ValueEnum};
+use futures::stream::{self, StreamExt};
 use num_format::{Locale, ToFormattedString};
 use rust_decimal::prelude::ToPrimitive;
-use rust_decimal::Decimal;
-use statrs::statistics::Statistics;
-use tabled::{builder::Builder, settings::Style};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tabled::{
+    builder::Builder,
+    settings::{style::HorizontalLine, Style},
+};
 use textplots::{Chart, Plot, Shape};
+use vol::{
+    calc_drawdowns, calc_metrics, calc_rebased, earnings_candle, fetch_report, fetch_shared,
+    retry_config, timed, Analysis, AnalyzeOptions, AnnualizationBasis, Backtest, BandPoint,
+    EarningsHistory, EarningsImpact, Exchange, FetchTimings, Keltner, Metrics, Position, PriceType,
+    QuoteOptions, RateSource, Reaction, Report, Seasonality, SeasonalityBasis, SeriesPoint, Shared,
+    VolEstimator, VwapBands, VwapPoint, DEFAULT_TZ, MIN_SEASON_SAMPLES, MONTHS, PARALLEL_THRESHOLD,
+};
 use yfinance_rs::core::conversions::money_to_f64;
 use yfinance_rs::fundamentals::CashflowRow;
-use yfinance_rs::{Candle, Interval, Range, Ticker, YfClientBuilder};
+use yfinance_rs::{Candle, Interval, Range, YfClientBuilder};
+
+use explain::Metric;
 
 const CHART_HEIGHT: u32 = 60;
 const CHART_WIDTH: u32 = 180;
-const TRADING_DAYS_YEAR: f64 = 252.0; // assume 252 trading days per year
 const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -51,114 +71,2162 @@ impl From<RangeArg> for Range {
     }
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "lowercase")]
+enum IntervalArg {
+    I1m,
+    I2m,
+    I5m,
+    I15m,
+    I30m,
+    I90m,
+    I1h,
+    D1,
+    D5,
+    W1,
+    M1,
+    M3,
+    // chosen from the range by auto_interval
+    Auto,
+}
+
+impl IntervalArg {
+    fn resolve(self, range: RangeArg) -> CandleInterval {
+        match self {
+            IntervalArg::Auto => auto_interval(range),
+            IntervalArg::I1m => CandleInterval::I1m,
+            IntervalArg::I2m => CandleInterval::I2m,
+            IntervalArg::I5m => CandleInterval::I5m,
+            IntervalArg::I15m => CandleInterval::I15m,
+            IntervalArg::I30m => CandleInterval::I30m,
+            IntervalArg::I90m => CandleInterval::I90m,
+            IntervalArg::I1h => CandleInterval::I1h,
+            IntervalArg::D1 => CandleInterval::D1,
+            IntervalArg::D5 => CandleInterval::D5,
+            IntervalArg::W1 => CandleInterval::W1,
+            IntervalArg::M1 => CandleInterval::M1,
+            IntervalArg::M3 => CandleInterval::M3,
+        }
+    }
+}
+
+/// `--interval` once `auto` has been resolved against the range.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[value(rename_all = "lowercase")]
+enum CandleInterval {
+    I1m,
+    I2m,
+    I5m,
+    I15m,
+    I30m,
+    I90m,
+    I1h,
+    D1,
+    D5,
+    W1,
+    M1,
+    M3,
+}
+
+impl From<CandleInterval> for Interval {
+    fn from(interval: CandleInterval) -> Self {
+        match interval {
+            CandleInterval::I1m => Interval::I1m,
+            CandleInterval::I2m => Interval::I2m,
+            CandleInterval::I5m => Interval::I5m,
+            CandleInterval::I15m => Interval::I15m,
+            CandleInterval::I30m => Interval::I30m,
+            CandleInterval::I90m => Interval::I90m,
+            CandleInterval::I1h => Interval::I1h,
+            CandleInterval::D1 => Interval::D1,
+            CandleInterval::D5 => Interval::D5,
+            CandleInterval::W1 => Interval::W1,
+            CandleInterval::M1 => Interval::M1,
+            CandleInterval::M3 => Interval::M3,
+        }
+    }
+}
+
+/// A sensible candle interval for a range: intraday under a week, then daily, weekly and monthly.
+fn auto_interval(range: RangeArg) -> CandleInterval {
+    match range {
+        RangeArg::D1 => CandleInterval::I5m,
+        RangeArg::D5 => CandleInterval::I30m,
+        RangeArg::M1
+        | RangeArg::M3
+        | RangeArg::M6
+        | RangeArg::Y1
+        | RangeArg::Y2
+        | RangeArg::Ytd => CandleInterval::D1,
+        RangeArg::Y5 | RangeArg::Y10 => CandleInterval::W1,
+        RangeArg::Max => CandleInterval::M1,
+    }
+}
+
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(short, long, required = true, help = "ticker symbol such as MSFT")]
-    ticker: String,
+    #[arg(
+        short,
+        long,
+        required_unless_present = "list_metrics",
+        value_delimiter = ',',
+        help = "ticker symbol such as MSFT, or a comma-separated watchlist"
+    )]
+    ticker: Vec<String>,
     #[arg(value_enum, short, long, default_value_t = RangeArg::M1, help = "historical time range")]
     range: RangeArg,
+    #[arg(
+        value_enum,
+        short,
+        long,
+        default_value_t = IntervalArg::D1,
+        help = "candle interval, or auto to pick one from the range"
+    )]
+    interval: IntervalArg,
+    #[arg(
+        long,
+        help = "include pre-market and post-market candles (intraday intervals only)"
+    )]
+    include_prepost: bool,
+    #[arg(long, help = "exclude extended-hours candles from the price analysis")]
+    regular_hours_only: bool,
+    #[arg(value_enum, short, long, default_value_t = OutputFormat::Table, help = "output format")]
+    format: OutputFormat,
+    #[arg(long, help = "include the candle array in jsonl output")]
+    candles: bool,
+    #[arg(
+        long,
+        help = "indent jsonl records for reading; records then span several lines"
+    )]
+    json_pretty: bool,
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..), help = "number of tickers fetched concurrently")]
+    concurrency: u16,
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        help = "benchmark ticker such as SPY, or a comma-separated list; the first is primary"
+    )]
+    benchmark: Vec<String>,
+    #[arg(
+        long,
+        value_name = "WINDOW",
+        requires = "benchmark",
+        value_parser = clap::value_parser!(u16).range(2..),
+        help = "rolling correlation with the benchmark over this many periods"
+    )]
+    rolling_corr: Option<u16>,
+    #[arg(
+        long,
+        value_name = "WINDOW",
+        value_parser = clap::value_parser!(u16).range(2..),
+        help = "rolling annualized Sharpe ratio over this many periods"
+    )]
+    rolling_sharpe: Option<u16>,
+    #[arg(
+        long,
+        help = "annualize CAGR over the calendar span (days / 365) instead of trading periods"
+    )]
+    calendar_annualization: bool,
+    #[arg(
+        value_enum,
+        long,
+        num_args = 0..=1,
+        default_missing_value = "return",
+        value_name = "METRIC",
+        help = "rank the watchlist by a metric instead of printing each ticker"
+    )]
+    rank: Option<RankMetric>,
+    #[arg(
+        long,
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "number of tickers in each ranking table"
+    )]
+    top: u16,
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "only display the most recent N candles"
+    )]
+    max_candles: Option<u64>,
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), help = "compute stats over the trailing N returns only")]
+    stats_window: Option<u32>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "save metrics to this JSON file and report changes since the previous run"
+    )]
+    snapshot: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "write one CSV row of headline metrics per ticker, failures included"
+    )]
+    batch_summary: Option<PathBuf>,
+    #[arg(long, help = "report the Ulcer Index")]
+    ulcer: bool,
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = VolEstimator::Close,
+        help = "annualized volatility estimator"
+    )]
+    vol_estimator: VolEstimator,
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        help = "write the report to this file instead of stdout"
+    )]
+    output: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "output",
+        help = "with --output, also print the report to stdout"
+    )]
+    tee: bool,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "14",
+        value_name = "PERIOD",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "report the Money Flow Index over PERIOD candles [default: 14]"
+    )]
+    mfi: Option<u16>,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "14",
+        value_name = "PERIOD",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "report the Chande Momentum Oscillator over PERIOD candles [default: 14]"
+    )]
+    cmo: Option<u16>,
+    #[arg(
+        value_enum,
+        long,
+        help = "price fed to --keltner, --bollinger, --cmo and --backtest [default: typical for Keltner, close otherwise]"
+    )]
+    price_type: Option<PriceType>,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "20",
+        value_name = "WINDOW",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "report the latest volume relative to the WINDOW-period average [default: 20]"
+    )]
+    relative_volume: Option<u16>,
+    #[arg(
+        long,
+        value_name = "PERIOD,MULT",
+        value_parser = parse_band,
+        help = "add Keltner channel columns: a PERIOD EMA of the typical price ± MULT PERIOD-ATRs, e.g. 20,2"
+    )]
+    keltner: Option<(u16, f64)>,
+    #[arg(
+        long,
+        value_name = "PERIOD,K",
+        value_parser = parse_band,
+        help = "add Bollinger band columns: a PERIOD mean of closes ± K standard deviations, e.g. 20,2"
+    )]
+    bollinger: Option<(u16, f64)>,
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        allow_negative_numbers = true,
+        help = "per-period return threshold for the Omega ratio, e.g. 0.001 for 0.1%"
+    )]
+    omega_threshold: f64,
+    #[arg(long, help = "print fetch and computation timings to stderr")]
+    timing: bool,
+    #[arg(
+        long,
+        help = "summarize returns around the earnings dates inside the window"
+    )]
+    earnings_impact: bool,
+    #[arg(
+        long,
+        help = "print past reported vs estimated EPS and annual earnings"
+    )]
+    earnings_history: bool,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "drop candles with volume below N (or no volume) before display and stats"
+    )]
+    min_volume: Option<u64>,
+    #[arg(
+        long,
+        default_value = "en",
+        value_parser = parse_locale,
+        help = "locale for digit grouping in volumes, e.g. en, de, fr"
+    )]
+    locale: Locale,
+    #[arg(
+        long,
+        help = "abbreviate large volumes and cash flows, e.g. 1.23B, 456.7M, 12.3K"
+    )]
+    compact_numbers: bool,
+    #[arg(
+        long,
+        value_name = "PRICE",
+        value_parser = parse_positive,
+        help = "report unrealized P&L of the latest close against this cost basis"
+    )]
+    cost_basis: Option<f64>,
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "cost_basis",
+        value_parser = parse_positive,
+        help = "number of shares held, to report P&L in dollars"
+    )]
+    shares: Option<f64>,
+    #[arg(
+        long,
+        value_name = "YYYY-MM-DD",
+        requires = "cost_basis",
+        help = "purchase date, to report the return since its candle (or the next trading day's)"
+    )]
+    buy_date: Option<NaiveDate>,
+    #[arg(
+        value_enum,
+        long,
+        num_args = 0..=1,
+        value_name = "BASIS",
+        help = "average return and win rate by weekday or calendar month [default: month for weekly or longer candles, weekday otherwise]"
+    )]
+    seasonality: Option<Option<SeasonalityBasis>>,
+    #[arg(
+        long,
+        help = "also compare against the ETF for the ticker's sector, e.g. XLK (SPY if unknown)"
+    )]
+    sector_compare: bool,
+    #[arg(
+        long,
+        default_value = "^IRX",
+        value_name = "SYMBOL",
+        help = "yield ticker used as the annualized risk-free rate"
+    )]
+    risk_free_ticker: String,
+    #[arg(
+        long,
+        value_name = "RATE",
+        allow_negative_numbers = true,
+        help = "annualized risk-free rate used if the ticker can't be fetched, e.g. 0.04 for 4% [default: 0]"
+    )]
+    risk_free_rate: Option<f64>,
+    // recorded candles for offline tests; see AnalyzeOptions::fixtures
+    #[arg(long, hide = true, value_name = "DIR")]
+    fixtures: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "abort on the first ticker that fails instead of reporting failures at the end"
+    )]
+    fail_fast: bool,
+    #[arg(
+        long,
+        value_name = "K",
+        value_parser = parse_positive,
+        help = "show session VWAP with bands K standard deviations wide (intraday intervals only)"
+    )]
+    vwap_bands: Option<f64>,
+    #[arg(
+        long,
+        value_name = "YYYY-MM-DD",
+        help = "report VWAP accumulated from the first candle on or after this date"
+    )]
+    anchored_vwap: Option<NaiveDate>,
+    #[arg(
+        value_enum,
+        long,
+        default_value_t = Exchange::Us,
+        help = "trading calendar used to warn about missing days"
+    )]
+    exchange: Exchange,
+    #[arg(
+        long,
+        help = "add a column with each row's drawdown from the running peak"
+    )]
+    show_drawdown: bool,
+    #[arg(
+        long,
+        help = "mark the candle each earnings date falls on (or the next trading day) with an E"
+    )]
+    earnings_markers: bool,
+    #[arg(
+        long,
+        help = "follow the headline metrics with a plain-English reading of each value"
+    )]
+    explain: bool,
+    #[arg(
+        long,
+        help = "after the reports, compare every ticker's closes rebased to 100 on their latest common start"
+    )]
+    rebase: bool,
+    #[arg(
+        long,
+        value_name = "sma-crossover:SHORT,LONG",
+        value_parser = parse_backtest,
+        help = "backtest buying when the SHORT SMA crosses above the LONG SMA and selling on the reverse cross, e.g. sma-crossover:20,50"
+    )]
+    backtest: Option<(u16, u16)>,
+    #[arg(
+        long,
+        value_name = "RATE",
+        requires = "backtest",
+        value_parser = parse_positive,
+        help = "commission on each backtest fill as a fraction of its value, e.g. 0.001 for 0.1% [default: 0]"
+    )]
+    commission: Option<f64>,
+    #[arg(
+        long,
+        help = "list every metric and indicator with the flag that enables it, then exit"
+    )]
+    list_metrics: bool,
+}
+
+impl Args {
+    /// The candle interval to fetch, with `--interval auto` picked from the range.
+    fn candle_interval(&self) -> CandleInterval {
+        self.interval.resolve(self.range)
+    }
+}
+
+fn parse_band(s: &str) -> Result<(u16, f64), String> {
+    let (period, mult) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected PERIOD,MULT such as 20,2, got '{}'", s))?;
+    let period: u16 = period
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid period '{}': {}", period, e))?;
+    if period < 2 {
+        return Err("period must be at least 2".to_string());
+    }
+    Ok((period, parse_positive(mult.trim())?))
+}
+
+fn parse_backtest(s: &str) -> Result<(u16, u16), String> {
+    let periods = s.strip_prefix("sma-crossover:").ok_or_else(|| {
+        format!(
+            "expected sma-crossover:SHORT,LONG such as sma-crossover:20,50, got '{}'",
+            s
+        )
+    })?;
+    let (short, long) = periods.split_once(',').ok_or_else(|| {
+        format!(
+            "expected SHORT,LONG after sma-crossover:, got '{}'",
+            periods
+        )
+    })?;
+    let parse = |p: &str| {
+        p.trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|&p| p > 0)
+            .ok_or_else(|| format!("invalid period '{}'", p))
+    };
+    let (short, long) = (parse(short)?, parse(long)?);
+    if short >= long {
+        return Err("the short period must be less than the long one".to_string());
+    }
+    Ok((short, long))
+}
+
+fn parse_positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        Ok(_) => Err("must be greater than zero".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
+fn parse_locale(name: &str) -> Result<Locale, String> {
+    Locale::from_name(name).map_err(|_| {
+        let prefix = name.split(['-', '_']).next().unwrap_or(name);
+        let similar: Vec<&str> = Locale::available_names()
+            .iter()
+            .copied()
+            .filter(|n| n.to_lowercase().starts_with(&prefix.to_lowercase()))
+            .take(10)
+            .collect();
+        if similar.is_empty() {
+            format!("unknown locale '{}' (try en, de, fr, es, it or ja)", name)
+        } else {
+            format!(
+                "unknown locale '{}'; similar supported locales: {}",
+                name,
+                similar.join(", ")
+            )
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[value(rename_all = "lowercase")]
+enum RankMetric {
+    Return,
+    Cagr,
+    Volatility,
+    Sortino,
+    Correlation,
+}
+
+impl RankMetric {
+    fn label(self) -> &'static str {
+        match self {
+            RankMetric::Return => "Return %",
+            RankMetric::Cagr => "CAGR %",
+            RankMetric::Volatility => "Volatility %",
+            RankMetric::Sortino => "Sortino",
+            RankMetric::Correlation => "Correlation",
+        }
+    }
+
+    fn value(self, metrics: &Metrics) -> Option<f64> {
+        match self {
+            RankMetric::Return => metrics.pct_change,
+            RankMetric::Cagr => metrics.cagr.map(|c| c * 100.0),
+            RankMetric::Volatility => metrics.annualized_vol,
+            RankMetric::Sortino => metrics.sortino,
+            RankMetric::Correlation => metrics.correlation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[value(rename_all = "lowercase")]
+enum OutputFormat {
+    Table,
+    Jsonl,
+}
+
+/// An optional column appended to the quote table, one cell per candle.
 #[derive(Debug)]
-struct PriceRange {
-    low: f64,
+struct Column {
+    header: String,
+    cells: Vec<String>,
+}
+
+/// Wall-clock time spent in each phase of a run, reported by `--timing`.
+#[derive(Debug, Default)]
+struct RunTimings {
+    shared: Duration,
+    tickers: Vec<(String, FetchTimings)>,
+    computation: Duration,
+}
+
+/// Metrics persisted between runs by `--snapshot`, keyed by ticker.
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    taken_at: DateTime<Utc>,
+    #[serde(default)]
+    tickers: BTreeMap<String, SnapshotEntry>,
+}
+
+// every field defaults so snapshots written by older or newer versions still load
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct SnapshotEntry {
+    last_close: Option<f64>,
+    pct_change: Option<f64>,
+    annualized_vol: Option<f64>,
+    sortino: Option<f64>,
+    earnings_date: Option<DateTime<Utc>>,
+}
+
+/// One --batch-summary row; the metrics are blank for a failed ticker.
+#[derive(Debug, Default)]
+struct SummaryRow {
+    metrics: Option<SummaryMetrics>,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+struct SummaryMetrics {
+    last_close: Option<f64>,
+    pct_change: Option<f64>,
+    annualized_vol: Option<f64>,
+    sharpe: Option<f64>,
+    max_drawdown: Option<f64>,
+    next_earnings: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct CandleRow {
+    ts: DateTime<Utc>,
+    open: f64,
     high: f64,
+    low: f64,
+    close: f64,
+    volume: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonLine<'a> {
+    symbol: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<&'a Metrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candles: Option<Vec<CandleRow>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl From<&Args> for QuoteOptions {
+    fn from(ags: &Args) -> Self {
+        let interval: Interval = ags.candle_interval().into();
+        QuoteOptions {
+            range: ags.range.into(),
+            interval,
+            prepost: ags.include_prepost && interval.is_intraday(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&Args> for AnalyzeOptions {
+    fn from(ags: &Args) -> Self {
+        AnalyzeOptions {
+            quotes: QuoteOptions::from(ags),
+            regular_hours_only: ags.regular_hours_only,
+            benchmark: ags.benchmark.clone(),
+            rolling_corr: ags.rolling_corr,
+            rolling_sharpe: ags.rolling_sharpe,
+            calendar_annualization: ags.calendar_annualization,
+            stats_window: ags.stats_window,
+            ulcer: ags.ulcer,
+            vol_estimator: ags.vol_estimator,
+            mfi: ags.mfi,
+            cmo: ags.cmo,
+            price_type: ags.price_type,
+            relative_volume: ags.relative_volume,
+            keltner: ags.keltner.map(|(period, mult)| (period, period, mult)),
+            bollinger: ags.bollinger,
+            omega_threshold: ags.omega_threshold,
+            earnings_impact: ags.earnings_impact,
+            earnings_history: ags.earnings_history,
+            min_volume: ags.min_volume,
+            cost_basis: ags.cost_basis,
+            shares: ags.shares,
+            buy_date: ags.buy_date,
+            seasonality: ags.seasonality.map(|basis| {
+                basis
+                    .unwrap_or_else(|| SeasonalityBasis::for_interval(ags.candle_interval().into()))
+            }),
+            sector_compare: ags.sector_compare,
+            risk_free_ticker: ags.risk_free_ticker.clone(),
+            risk_free_rate: ags.risk_free_rate,
+            fixtures: ags.fixtures.clone(),
+            parallel_threshold: PARALLEL_THRESHOLD,
+            vwap_bands: ags.vwap_bands,
+            anchored_vwap: ags.anchored_vwap,
+            backtest: ags.backtest,
+            commission: ags.commission.unwrap_or(0.0),
+            exchange: ags.exchange,
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let ags = Args::parse();
-    let range: Range = ags.range.into();
-    let client = YfClientBuilder::default().user_agent(USER_AGENT).build()?;
-    let ticker = Ticker::new(&client, &ags.ticker);
-
-    let (quotes, earnings, fi, cf, risk_free_rate) = tokio::join!(
-        get_quotes(&ticker, range),
-        get_earnings_dates(&ticker),
-        ticker.fast_info(),
-        ticker.cashflow(None),
-        get_risk_free_rate(&client),
+    if matches!(ags.interval, IntervalArg::Auto) {
+        let name = ags
+            .candle_interval()
+            .to_possible_value()
+            .expect("not skipped");
+        eprintln!("--interval auto: using {}", name.get_name());
+    }
+    if ags.list_metrics {
+        return catalog::print(&mut io::stdout(), &Args::command());
+    }
+    let opts = AnalyzeOptions::from(&ags);
+    let client = YfClientBuilder::default()
+        .user_agent(USER_AGENT)
+        .retry_config(retry_config())
+        .build()?;
+    let concurrency = ags.concurrency as usize;
+    if ags.rank.is_some() && ags.format != OutputFormat::Table {
+        anyhow::bail!("--rank is only supported with --format table");
+    }
+    if ags.rebase && (ags.format != OutputFormat::Table || ags.rank.is_some()) {
+        anyhow::bail!("--rebase is only supported with --format table and without --rank");
+    }
+    if ags.seasonality.is_some() && Interval::from(ags.candle_interval()).is_intraday() {
+        anyhow::bail!("--seasonality needs a daily or longer --interval");
+    }
+    let multi_year = matches!(
+        ags.range,
+        RangeArg::Y2 | RangeArg::Y5 | RangeArg::Y10 | RangeArg::Max
+    );
+    if opts.seasonality == Some(SeasonalityBasis::Month) && !multi_year {
+        eprintln!("--seasonality by month compares several years; try a --range of 5y or more");
+    }
+    if ags.vwap_bands.is_some() && !Interval::from(ags.candle_interval()).is_intraday() {
+        eprintln!("--vwap-bands only applies to intraday intervals; ignoring it");
+    }
+
+    let started = Instant::now();
+    let mut timings = RunTimings::default();
+    let mut out = open_output(ags.output.as_deref(), ags.tee)?;
+    let previous = ags.snapshot.as_deref().and_then(load_snapshot);
+    let mut entries = BTreeMap::new();
+    let mut summaries = HashMap::new();
+    // (symbol, error) for tickers skipped when not failing fast
+    let mut failures = vec![];
+    // (symbol, candles, exchange tz) kept for --rebase
+    let mut rebase = vec![];
+
+    match ags.format {
+        OutputFormat::Table if ags.rank.is_some() => {
+            let (reports, shared) = tokio::join!(
+                stream::iter(&ags.ticker)
+                    .map(|symbol| async {
+                        (symbol.as_str(), fetch_report(&client, symbol, &opts).await)
+                    })
+                    .buffered(concurrency)
+                    .collect::<Vec<_>>(),
+                timed(fetch_shared(&client, &opts)),
+            );
+            let (shared, elapsed) = shared;
+            let shared = shared?;
+            timings.shared = elapsed;
+            for (symbol, report) in &reports {
+                if let Err(e) = report {
+                    if ags.fail_fast {
+                        anyhow::bail!("could not fetch {}: {:#}", symbol.to_uppercase(), e);
+                    }
+                    failures.push((symbol.to_uppercase(), format!("{:#}", e)));
+                }
+            }
+            let compute = Instant::now();
+            let analyses: Vec<(&str, Result<Analysis>)> = reports
+                .into_iter()
+                .map(|(symbol, report)| (symbol, report.map(|r| analysis(r, &opts, &shared))))
+                .collect();
+            print_ranking(&mut out, &analyses, &ags)?;
+            for (_, analysis) in &analyses {
+                if let Ok(Analysis { report, metrics }) = analysis {
+                    entries.insert(report.symbol.clone(), snapshot_entry(report, metrics));
+                    if ags.batch_summary.is_some() {
+                        summaries.insert(report.symbol.clone(), summary_row(report, metrics));
+                    }
+                    timings
+                        .tickers
+                        .push((report.symbol.clone(), report.timings));
+                }
+            }
+            if let Some(previous) = &previous {
+                print_snapshot_changes(&mut out, &snapshot_changes(previous, &entries))?;
+            }
+            timings.computation = compute.elapsed();
+        }
+        OutputFormat::Table => {
+            let (reports, shared) = tokio::join!(
+                stream::iter(&ags.ticker)
+                    .map(|symbol| fetch_report(&client, symbol, &opts))
+                    .buffered(concurrency)
+                    .collect::<Vec<_>>(),
+                timed(fetch_shared(&client, &opts)),
+            );
+            let (shared, elapsed) = shared;
+            let shared = shared?;
+            timings.shared = elapsed;
+            for (symbol, report) in ags.ticker.iter().zip(reports) {
+                let report = match report {
+                    Ok(report) => report,
+                    Err(e) if ags.fail_fast => {
+                        return Err(e.context(format!("could not fetch {}", symbol.to_uppercase())));
+                    }
+                    Err(e) => {
+                        failures.push((symbol.to_uppercase(), format!("{:#}", e)));
+                        continue;
+                    }
+                };
+                if !entries.is_empty() {
+                    writeln!(out, "\n")?;
+                }
+                let compute = Instant::now();
+                let metrics = calc_metrics(&report, &opts, &shared);
+                print_report(&mut out, &report, &metrics, &ags, &opts)?;
+                entries.insert(report.symbol.clone(), snapshot_entry(&report, &metrics));
+                if ags.batch_summary.is_some() {
+                    summaries.insert(report.symbol.clone(), summary_row(&report, &metrics));
+                }
+                timings.computation += compute.elapsed();
+                timings
+                    .tickers
+                    .push((report.symbol.clone(), report.timings));
+                if ags.rebase {
+                    rebase.push((report.symbol, report.quotes, report.tz));
+                }
+            }
+            if ags.rebase {
+                print_rebased(&mut out, &rebase, &ags)?;
+            }
+            if let Some(previous) = &previous {
+                print_snapshot_changes(&mut out, &snapshot_changes(previous, &entries))?;
+            }
+        }
+        OutputFormat::Jsonl => {
+            // emit each ticker as soon as its fetch completes so consumers can start early
+            let mut fetches = stream::iter(&ags.ticker)
+                .map(|symbol| async {
+                    (symbol.as_str(), fetch_report(&client, symbol, &opts).await)
+                })
+                .buffer_unordered(concurrency);
+            // tickers keep fetching alongside the shared data; any done first wait for it
+            let mut early = vec![];
+            let shared = timed(fetch_shared(&client, &opts));
+            tokio::pin!(shared);
+            let (shared, elapsed) = loop {
+                tokio::select! {
+                    shared = &mut shared => break shared,
+                    Some(fetched) = fetches.next() => early.push(fetched),
+                }
+            };
+            let shared = shared?;
+            timings.shared = elapsed;
+            let mut pending = stream::iter(early).chain(fetches);
+            while let Some((symbol, report)) = pending.next().await {
+                let compute = Instant::now();
+                let analysis = report.map(|r| analysis(r, &opts, &shared));
+                print_json_line(&mut out, symbol, &analysis, &ags)?;
+                match analysis {
+                    Ok(Analysis { report, metrics }) => {
+                        entries.insert(report.symbol.clone(), snapshot_entry(&report, &metrics));
+                        if ags.batch_summary.is_some() {
+                            summaries.insert(report.symbol.clone(), summary_row(&report, &metrics));
+                        }
+                        timings
+                            .tickers
+                            .push((report.symbol.clone(), report.timings));
+                    }
+                    Err(e) if ags.fail_fast => {
+                        return Err(e.context(format!("could not fetch {}", symbol.to_uppercase())));
+                    }
+                    Err(e) => failures.push((symbol.to_uppercase(), format!("{:#}", e))),
+                }
+                timings.computation += compute.elapsed();
+            }
+            // the delta goes last, as a record of its own
+            if let Some(previous) = &previous {
+                let changes = snapshot_changes(previous, &entries);
+                let record = serde_json::json!({ "snapshot_changes": changes });
+                writeln!(out, "{}", serde_json::to_string(&record)?)?;
+            }
+        }
+    }
+
+    if let Some(path) = &ags.snapshot {
+        // keep tickers from earlier runs that weren't part of this one
+        let mut tickers = previous.map(|p| p.tickers).unwrap_or_default();
+        tickers.extend(entries);
+        save_snapshot(
+            path,
+            &Snapshot {
+                taken_at: Utc::now(),
+                tickers,
+            },
+        )?;
+    }
+    if let Some(path) = &ags.batch_summary {
+        for (symbol, e) in &failures {
+            let row = SummaryRow {
+                metrics: None,
+                error: Some(e.clone()),
+            };
+            summaries.insert(symbol.clone(), row);
+        }
+        write_batch_summary(path, &ags.ticker, &summaries)?;
+    }
+    out.flush()?;
+    if ags.timing {
+        print_timings(&timings, started.elapsed());
+    }
+    if !failures.is_empty() {
+        eprintln!(
+            "\n--- Failed {} of {} tickers ---",
+            failures.len(),
+            ags.ticker.len()
+        );
+        for (symbol, e) in &failures {
+            eprintln!("{}: {}", symbol, e);
+        }
+        // a partial run still succeeds; only a run with nothing to show is an error
+        if failures.len() == ags.ticker.len() {
+            anyhow::bail!("every ticker failed");
+        }
+    }
+    Ok(())
+}
+
+fn print_timings(timings: &RunTimings, total: Duration) {
+    eprintln!("\n--- Timing ---");
+    eprintln!(
+        "Shared data (risk-free rate, benchmarks): {:.3}s",
+        timings.shared.as_secs_f64()
     );
-    let fi = fi?;
-    let quotes = quotes?;
-    let earnings = earnings.ok();
-    let cf = cf?;
-    let risk_free_rate = risk_free_rate?;
-
-    if let Some(name) = fi.name {
-        println!("{} ({})", name, &ags.ticker.to_uppercase());
-    }
-
-    let returns = calc_returns(&quotes);
-    print_quotes(&quotes, &returns);
-
-    println!("\n");
-    display_plot(&quotes);
-
-    println!("\n--- Price Analysis ---");
-    if quotes.len() >= 2 {
-        let initial_close = quotes[0].close.amount();
-        if initial_close != Decimal::ZERO {
-            let pct_chg = Decimal::from(100)
-                * (quotes[quotes.len() - 1].close.amount() - initial_close)
-                / initial_close;
-            println!("Pct change over period: {:.2}", pct_chg);
-        }
-    }
-
-    if quotes.len() >= 3 {
-        // need at least 3 data points to calculate std dev
-        let std_dev = returns.as_slice().std_dev();
-        let annualized_vol = std_dev * TRADING_DAYS_YEAR.sqrt() * 100.0;
-        let sortino = sortino_ratio(&returns, risk_free_rate);
-        println!("Std dev of returns: {:.4}", std_dev);
-        println!("Annualized volatility: {:.2}", annualized_vol);
-        println!(
-            "Sortino ratio: {:.2} (using risk free rate of {:.2}%)",
+    for (symbol, t) in &timings.tickers {
+        eprintln!(
+            "{}: fetch {:.3}s (quotes {:.3}s, earnings {:.3}s)",
+            symbol,
+            t.total.as_secs_f64(),
+            t.quotes.as_secs_f64(),
+            t.earnings.as_secs_f64()
+        );
+    }
+    if timings.tickers.len() > 1 {
+        // tickers are fetched concurrently, so the sum can exceed the wall time
+        let sum: Duration = timings.tickers.iter().map(|(_, t)| t.total).sum();
+        let slowest = timings
+            .tickers
+            .iter()
+            .map(|(_, t)| t.total)
+            .max()
+            .unwrap_or_default();
+        eprintln!(
+            "Ticker fetches: {:.3}s combined, {:.3}s slowest",
+            sum.as_secs_f64(),
+            slowest.as_secs_f64()
+        );
+    }
+    eprintln!("Computation: {:.3}s", timings.computation.as_secs_f64());
+    eprintln!("Total: {:.3}s", total.as_secs_f64());
+}
+
+/// Writes everything to two sinks, like the unix `tee` command.
+struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.first.write_all(buf)?;
+        self.second.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.first.flush()?;
+        self.second.flush()
+    }
+}
+
+fn open_output(path: Option<&Path>, tee: bool) -> Result<Box<dyn Write>> {
+    let Some(path) = path else {
+        return Ok(Box::new(std::io::stdout()));
+    };
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("could not create {}", parent.display()))?;
+        }
+    }
+    let file =
+        File::create(path).with_context(|| format!("could not create {}", path.display()))?;
+    let file = BufWriter::new(file);
+    if tee {
+        return Ok(Box::new(Tee {
+            first: file,
+            second: std::io::stdout(),
+        }));
+    }
+    Ok(Box::new(file))
+}
+
+fn print_report(
+    out: &mut dyn Write,
+    report: &Report,
+    metrics: &Metrics,
+    ags: &Args,
+    opts: &AnalyzeOptions,
+) -> Result<()> {
+    let interval: Interval = ags.candle_interval().into();
+    let prepost = opts.prepost();
+
+    if let Some(name) = &report.name {
+        writeln!(out, "{} ({})", name, report.symbol)?;
+    }
+    if interval.is_intraday() {
+        writeln!(
+            out,
+            "Sessions: {}",
+            session_summary(prepost, ags.regular_hours_only)
+        )?;
+    }
+
+    if let Some(min) = ags.min_volume {
+        writeln!(
+            out,
+            "Filtered out {} candles with volume below {}",
+            report.filtered,
+            min.to_formatted_string(&ags.locale)
+        )?;
+    }
+
+    if let Some(coverage) = report.coverage.as_ref().filter(|c| !c.missing.is_empty()) {
+        // list a few dates; a long gap is usually one missing stretch
+        let mut dates: Vec<String> = coverage
+            .missing
+            .iter()
+            .take(5)
+            .map(|d| d.to_string())
+            .collect();
+        if coverage.missing.len() > dates.len() {
+            dates.push("...".to_string());
+        }
+        writeln!(
+            out,
+            "Warning: missing data for {} of {} expected trading days ({})",
+            coverage.missing.len(),
+            coverage.expected,
+            dates.join(", ")
+        )?;
+    }
+
+    let mut columns = vec![];
+    if prepost {
+        columns.push(Column {
+            header: "Session".to_string(),
+            cells: report
+                .sessions
+                .iter()
+                .map(|s| s.label().to_string())
+                .collect(),
+        });
+    }
+    if let Some(rolling) = &metrics.rolling_corr {
+        columns.push(series_column(
+            "Roll Corr",
+            &report.quotes,
+            &rolling.points,
+            "n/a",
+        ));
+    }
+    if let Some(rolling) = &metrics.rolling_sharpe {
+        // zero-variance windows are left blank rather than shown as inf
+        columns.push(series_column(
+            "Roll Sharpe",
+            &report.quotes,
+            &rolling.points,
+            "",
+        ));
+    }
+    if let Some(bands) = &metrics.vwap_bands {
+        columns.extend(vwap_columns(&report.quotes, bands));
+    }
+    if let Some(k) = &metrics.keltner {
+        columns.extend(band_columns("KC", &report.quotes, &k.points, true));
+    }
+    if let Some(b) = &metrics.bollinger {
+        columns.extend(band_columns("BB", &report.quotes, &b.points, false));
+    }
+    if let Some(squeeze) = &metrics.squeeze {
+        columns.push(Column {
+            header: "Squeeze".to_string(),
+            cells: report
+                .quotes
+                .iter()
+                .map(|q| {
+                    if squeeze.points.contains(&q.ts) {
+                        "yes"
+                    } else {
+                        ""
+                    }
+                    .to_string()
+                })
+                .collect(),
+        });
+    }
+    if ags.show_drawdown {
+        columns.push(drawdown_column(&report.quotes));
+    }
+    if ags.earnings_markers {
+        let dates = report.earnings.as_deref().unwrap_or_default();
+        columns.push(earnings_column(&report.quotes, report.tz, dates));
+    }
+    let start = match ags.max_candles.map(|n| n as usize) {
+        Some(n) if n < report.quotes.len() => {
+            writeln!(
+                out,
+                "Showing the most recent {} of {} candles",
+                n,
+                report.quotes.len()
+            )?;
+            report.quotes.len() - n
+        }
+        _ => 0,
+    };
+    print_quotes(
+        out,
+        &report.quotes,
+        &report.returns,
+        report.tz,
+        &columns,
+        start,
+        ags,
+    )?;
+
+    writeln!(out, "\n")?;
+    display_plot(out, &report.quotes)?;
+
+    print_analysis(out, metrics, ags)?;
+    print_cashflow(out, &report.cashflow, ags.compact_numbers)?;
+    Ok(())
+}
+
+fn series_column(
+    header: &str,
+    quotes: &[Candle],
+    points: &[SeriesPoint],
+    undefined: &str,
+) -> Column {
+    // rows before the first full window stay blank; windows without a value show `undefined`
+    let by_ts: HashMap<DateTime<Utc>, Option<f64>> =
+        points.iter().map(|p| (p.ts, p.value)).collect();
+    let cells = quotes
+        .iter()
+        .map(|q| match by_ts.get(&q.ts) {
+            Some(Some(v)) => fmt_signed(*v),
+            Some(None) => undefined.to_string(),
+            None => "".to_string(),
+        })
+        .collect();
+    Column {
+        header: header.to_string(),
+        cells,
+    }
+}
+
+fn band_columns(
+    prefix: &str,
+    quotes: &[Candle],
+    points: &[BandPoint],
+    middle: bool,
+) -> Vec<Column> {
+    // rows in the warm-up period stay blank
+    let by_ts: HashMap<DateTime<Utc>, &BandPoint> = points.iter().map(|p| (p.ts, p)).collect();
+    let column = |name: &str, value: fn(&BandPoint) -> Option<f64>| Column {
+        header: format!("{} {}", prefix, name),
+        cells: quotes
+            .iter()
+            .map(|q| {
+                by_ts
+                    .get(&q.ts)
+                    .and_then(|p| value(p))
+                    .map_or_else(String::new, |v| format!("{:.2}", v))
+            })
+            .collect(),
+    };
+    let mut columns = vec![column("Low", |p| p.lower)];
+    if middle {
+        columns.push(column("Mid", |p| p.middle));
+    }
+    columns.push(column("High", |p| p.upper));
+    columns
+}
+
+fn earnings_column(quotes: &[Candle], tz: Option<Tz>, earnings: &[DateTime<Utc>]) -> Column {
+    let mut cells = vec![String::new(); quotes.len()];
+    for idx in earnings
+        .iter()
+        .filter_map(|er| earnings_candle(quotes, tz, er))
+    {
+        cells[idx] = "E".to_string();
+    }
+    Column {
+        header: "Earn".to_string(),
+        cells,
+    }
+}
+
+fn drawdown_column(quotes: &[Candle]) -> Column {
+    // the same peak walk as the max drawdown, shown per row with the deepest row marked
+    let drawdowns = calc_drawdowns(quotes);
+    let deepest = drawdowns
+        .iter()
+        .enumerate()
+        .filter(|(_, dd)| **dd > 0.0)
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(idx, _)| idx);
+    let cells = drawdowns
+        .iter()
+        .enumerate()
+        .map(|(idx, dd)| {
+            if Some(idx) == deepest {
+                format!("{:.2} max", dd)
+            } else {
+                format!("{:.2}", dd)
+            }
+        })
+        .collect();
+    Column {
+        header: "DD %".to_string(),
+        cells,
+    }
+}
+
+fn vwap_columns(quotes: &[Candle], bands: &VwapBands) -> Vec<Column> {
+    let by_ts: HashMap<DateTime<Utc>, &VwapPoint> =
+        bands.points.iter().map(|p| (p.ts, p)).collect();
+    let price = |value: fn(&VwapPoint) -> Option<f64>| -> Vec<String> {
+        quotes
+            .iter()
+            .map(|q| {
+                by_ts
+                    .get(&q.ts)
+                    .and_then(|p| value(p))
+                    .map_or_else(String::new, |v| format!("{:.2}", v))
+            })
+            .collect()
+    };
+    // flag closes outside the bands
+    let pierced = quotes
+        .iter()
+        .map(|q| {
+            let close = money_to_f64(&q.close);
+            match by_ts.get(&q.ts) {
+                Some(p) if p.upper.is_some_and(|u| close > u) => "above".to_string(),
+                Some(p) if p.lower.is_some_and(|l| close < l) => "below".to_string(),
+                _ => "".to_string(),
+            }
+        })
+        .collect();
+    vec![
+        Column {
+            header: "VWAP".to_string(),
+            cells: price(|p| p.vwap),
+        },
+        Column {
+            header: format!("+{}σ", bands.k),
+            cells: price(|p| p.upper),
+        },
+        Column {
+            header: format!("-{}σ", bands.k),
+            cells: price(|p| p.lower),
+        },
+        Column {
+            header: "Band".to_string(),
+            cells: pierced,
+        },
+    ]
+}
+
+fn print_rebased(
+    out: &mut dyn Write,
+    series: &[(String, Vec<Candle>, Option<Tz>)],
+    ags: &Args,
+) -> Result<()> {
+    let intraday = Interval::from(ags.candle_interval()).is_intraday();
+    writeln!(out)?;
+    let Some(rebased) = calc_rebased(series, intraday) else {
+        writeln!(out, "No common dates to rebase on")?;
+        return Ok(());
+    };
+    let date = |ts: DateTime<Utc>| {
+        if intraday {
+            ts.with_timezone(&DEFAULT_TZ)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        } else {
+            ts.date_naive().to_string()
+        }
+    };
+    writeln!(out, "--- Rebased to 100 on {} ---", date(rebased.start))?;
+    let mut builder = Builder::default();
+    let mut header = vec!["Date".to_string()];
+    header.extend(rebased.symbols.iter().cloned());
+    builder.push_record(header);
+    for (ts, values) in &rebased.rows {
+        let mut row = vec![date(*ts)];
+        row.extend(values.iter().map(|v| fmt_opt(*v)));
+        builder.push_record(row);
+    }
+    writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+    for (idx, symbol) in rebased.symbols.iter().enumerate() {
+        let last = rebased
+            .rows
+            .iter()
+            .rev()
+            .find_map(|(_, values)| values[idx]);
+        if let Some(last) = last {
+            writeln!(
+                out,
+                "{} ended at {:.2} ({:+.2}%)",
+                symbol,
+                last,
+                last - 100.0
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn print_ranking(
+    out: &mut dyn Write,
+    analyses: &[(&str, Result<Analysis>)],
+    ags: &Args,
+) -> Result<()> {
+    let metric = ags.rank.unwrap_or(RankMetric::Return);
+    let mut ranked = vec![];
+    let mut no_data = vec![];
+    for (symbol, analysis) in analyses {
+        let value = analysis
+            .as_ref()
+            .ok()
+            .and_then(|a| metric.value(&a.metrics));
+        match value {
+            Some(v) if v.is_finite() => ranked.push((symbol.to_uppercase(), v)),
+            _ => no_data.push(symbol.to_uppercase()),
+        }
+    }
+
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let rank_table = |rows: &mut dyn Iterator<Item = &(String, f64)>| {
+        let mut builder = Builder::default();
+        builder.push_record(["#", "Ticker", metric.label()]);
+        for (idx, (symbol, value)) in rows.enumerate() {
+            builder.push_record([(idx + 1).to_string(), symbol.clone(), fmt_signed(*value)]);
+        }
+        builder.build().with(Style::sharp()).to_string()
+    };
+    let top = (ags.top as usize).min(ranked.len());
+    writeln!(out, "Top {} by {}", top, metric.label())?;
+    writeln!(out, "{}", rank_table(&mut ranked.iter().take(top)))?;
+    // the bottom table only holds tickers the top one left out
+    let rest = &ranked[top..];
+    if !rest.is_empty() {
+        let bottom = rest.len().min(top);
+        writeln!(out)?;
+        writeln!(out, "Bottom {} by {}", bottom, metric.label())?;
+        writeln!(out, "{}", rank_table(&mut rest.iter().rev().take(bottom)))?;
+    }
+
+    if !no_data.is_empty() {
+        writeln!(out, "\nNo data: {}", no_data.join(", "))?;
+    }
+    Ok(())
+}
+
+fn print_analysis(out: &mut dyn Write, metrics: &Metrics, ags: &Args) -> Result<()> {
+    writeln!(out, "\n--- Price Analysis ---")?;
+    if let Some(window) = metrics.stats_window {
+        writeln!(out, "Stats use the trailing {} returns", window)?;
+    }
+    if !metrics.skipped.is_empty() {
+        let reasons: Vec<String> = metrics
+            .skipped
+            .iter()
+            .map(|s| {
+                format!(
+                    "{} skipped: need ≥{} candles, have {}",
+                    s.metrics, s.need, s.have
+                )
+            })
+            .collect();
+        writeln!(out, "Note: {}", reasons.join("; "))?;
+    }
+    if let Some(pct_chg) = metrics.pct_change {
+        writeln!(out, "Pct change over period: {:.2}", pct_chg)?;
+    }
+    if let (Some(cagr), Some(basis)) = (metrics.cagr, metrics.cagr_basis) {
+        let basis = match basis {
+            AnnualizationBasis::Trading => "trading periods",
+            AnnualizationBasis::Calendar => "calendar days / 365",
+        };
+        writeln!(out, "CAGR: {:.2} (annualized over {})", cagr * 100.0, basis)?;
+    }
+
+    if let (Some(std_dev), Some(annualized_vol), Some(sortino)) =
+        (metrics.std_dev, metrics.annualized_vol, metrics.sortino)
+    {
+        writeln!(out, "Std dev of returns: {:.4}", std_dev)?;
+        if matches!(ags.vol_estimator, VolEstimator::Close | VolEstimator::All) {
+            writeln!(out, "Annualized volatility: {:.2}", annualized_vol)?;
+            explain(out, ags, Metric::Volatility, Some(annualized_vol))?;
+        }
+        let source = match &metrics.risk_free_source {
+            RateSource::Ticker(symbol) => format!("from {}", symbol),
+            RateSource::Fallback => "from --risk-free-rate".to_string(),
+            RateSource::Zero => "as no rate was available".to_string(),
+        };
+        writeln!(
+            out,
+            "Sortino ratio: {:.2} (using risk free rate of {:.2}% {})",
             sortino,
-            risk_free_rate * 100.0
+            metrics.risk_free_rate * 100.0,
+            source
+        )?;
+        explain(out, ags, Metric::Sortino, Some(sortino))?;
+        writeln!(out, "Sharpe ratio: {}", fmt_opt(metrics.sharpe))?;
+        explain(out, ags, Metric::Sharpe, metrics.sharpe)?;
+    }
+    if let Some(vol) = metrics.parkinson_vol {
+        writeln!(out, "Annualized volatility (Parkinson): {:.2}", vol)?;
+        explain(out, ags, Metric::Volatility, Some(vol))?;
+    }
+    if let Some(vol) = metrics.garman_klass_vol {
+        writeln!(out, "Annualized volatility (Garman-Klass): {:.2}", vol)?;
+        explain(out, ags, Metric::Volatility, Some(vol))?;
+    }
+    // computed along with the Sortino ratio, but undefined when every return is on the threshold
+    if metrics.sortino.is_some() {
+        // no returns below the threshold means there is nothing to divide by
+        let omega = match metrics.omega {
+            Some(omega) if omega.is_infinite() => "inf".to_string(),
+            omega => fmt_opt(omega),
+        };
+        writeln!(
+            out,
+            "Omega ratio: {} (threshold {:.2}%)",
+            omega,
+            metrics.omega_threshold * 100.0
+        )?;
+        explain(out, ags, Metric::Omega, metrics.omega)?;
+    }
+
+    if let Some(dd) = metrics.max_drawdown {
+        writeln!(out, "Max drawdown: {:.2}", dd)?;
+        explain(out, ags, Metric::MaxDrawdown, Some(dd))?;
+    }
+    if let Some(dur) = &metrics.drawdown_duration {
+        let current = match dur.underwater_since {
+            Some(since) => format!("underwater since {}", since.date_naive()),
+            None => "at the period high".to_string(),
+        };
+        writeln!(
+            out,
+            "Longest drawdown: {} candle{} ({}\u{2013}{}{}); current: {}",
+            dur.longest,
+            if dur.longest == 1 { "" } else { "s" },
+            dur.start.date_naive(),
+            dur.end.date_naive(),
+            if dur.recovered { "" } else { ", not recovered" },
+            current
+        )?;
+    } else if metrics.max_drawdown.is_some() {
+        writeln!(out, "Longest drawdown: none")?;
+    }
+    if let Some(ulcer) = metrics.ulcer_index {
+        writeln!(out, "Ulcer index: {:.2}", ulcer)?;
+    }
+
+    if let Some(mfi) = &metrics.mfi {
+        let mut line = format!("Money flow index ({}): {}", mfi.period, fmt_opt(mfi.value));
+        if mfi.skipped > 0 {
+            line += &format!(" (skipped {} candles with no volume)", mfi.skipped);
+        }
+        writeln!(out, "{}", line)?;
+        explain(out, ags, Metric::MoneyFlow, mfi.value)?;
+    }
+    if let Some(cmo) = &metrics.cmo {
+        let label = match cmo.value {
+            Some(v) if v >= 50.0 => " (overbought)",
+            Some(v) if v <= -50.0 => " (oversold)",
+            _ => "",
+        };
+        writeln!(
+            out,
+            "Chande momentum ({}): {}{}",
+            cmo.period,
+            fmt_opt(cmo.value),
+            label
+        )?;
+        explain(out, ags, Metric::Chande, cmo.value)?;
+    }
+    if let Some(k) = &metrics.keltner {
+        print_keltner(out, k)?;
+    }
+    if let Some(b) = &metrics.bollinger {
+        let latest = b.points.last();
+        writeln!(
+            out,
+            "Bollinger bands ({}, {}σ): {} / {} / {}",
+            b.period,
+            b.k,
+            fmt_price(latest.and_then(|p| p.lower)),
+            fmt_price(latest.and_then(|p| p.middle)),
+            fmt_price(latest.and_then(|p| p.upper))
+        )?;
+    }
+    if let Some(squeeze) = &metrics.squeeze {
+        writeln!(
+            out,
+            "Squeeze (Bollinger inside Keltner): {} candles{}",
+            squeeze.points.len(),
+            if squeeze.active { ", active now" } else { "" }
+        )?;
+    }
+    if let Some(bands) = &metrics.vwap_bands {
+        let latest = bands.points.last().and_then(|p| p.vwap);
+        writeln!(
+            out,
+            "VWAP: {} (±{}σ bands pierced by {} closes above, {} below)",
+            latest.map_or_else(|| "n/a".to_string(), |v| format!("{:.2}", v)),
+            bands.k,
+            bands.above,
+            bands.below
+        )?;
+    }
+    if let Some(av) = &metrics.anchored_vwap {
+        writeln!(
+            out,
+            "Anchored VWAP from {}: {} (close {:.2}, {}%)",
+            av.anchor.date_naive(),
+            fmt_opt(av.vwap),
+            av.close,
+            av.deviation_pct
+                .map_or_else(|| "n/a".to_string(), |d| format!("{:+.2}", d))
+        )?;
+    }
+    if let Some(rv) = &metrics.relative_volume {
+        let value = rv
+            .value
+            .map_or_else(|| "n/a".to_string(), |v| format!("{:.2}x", v));
+        let mut line = format!("Relative volume: {} ({}-period avg)", value, rv.window);
+        if rv.skipped > 0 {
+            line += &format!(" (skipped {} candles with no volume)", rv.skipped);
+        }
+        writeln!(out, "{}", line)?;
+        explain(out, ags, Metric::RelativeVolume, rv.value)?;
+    }
+    if let Some(dv) = &metrics.dollar_volume {
+        let dollars = |v: f64| fmt_amount(v, ags);
+        let per = match ags.candle_interval().into() {
+            Interval::D1 => "daily",
+            _ => "per-candle",
+        };
+        let mut line = format!(
+            "avg {} $ volume: {} (median {})",
+            per,
+            dollars(dv.average),
+            dollars(dv.median)
         );
+        if dv.skipped > 0 {
+            line += &format!(" (skipped {} candles with no volume)", dv.skipped);
+        }
+        writeln!(out, "{}", line)?;
     }
 
-    if let Some((intraday, closing)) = get_price_range(&quotes) {
-        println!(
+    if let (Some(intraday), Some(closing)) = (&metrics.intraday_range, &metrics.closing_range) {
+        writeln!(
+            out,
             "Intraday low and high: {:.2} to {:.2}",
             intraday.low, intraday.high
-        );
-        println!(
+        )?;
+        writeln!(
+            out,
             "Closing low and high:  {:.2} to {:.2}",
             closing.low, closing.high
+        )?;
+    }
+    if let Some(pct) = metrics.pct_below_intraday_high {
+        writeln!(out, "Pct below intraday high for period: {:.2}", pct)?
+    }
+    if let Some(pct) = metrics.pct_below_closing_high {
+        writeln!(out, "Pct below closing high for period: {:.2}", pct)?
+    }
+
+    if let Some(etf) = &metrics.sector_etf {
+        writeln!(
+            out,
+            "Sector: {} (compared against {})",
+            metrics.sector.as_deref().unwrap_or("unknown"),
+            etf
+        )?;
+    }
+    if let Some(bench) = &metrics.benchmark {
+        writeln!(
+            out,
+            "Correlation with {}: {}",
+            bench,
+            fmt_opt(metrics.correlation)
+        )?;
+        explain(out, ags, Metric::Correlation, metrics.correlation)?;
+        writeln!(
+            out,
+            "Beta vs {}: {} (up-beta: {}, down-beta: {})",
+            bench,
+            fmt_opt(metrics.beta),
+            fmt_opt(metrics.up_beta),
+            fmt_opt(metrics.down_beta)
+        )?;
+        explain(out, ags, Metric::Beta, metrics.beta)?;
+        writeln!(
+            out,
+            "Upside capture vs {}: {}%, downside capture: {}%",
+            bench,
+            fmt_opt(metrics.upside_capture),
+            fmt_opt(metrics.downside_capture)
+        )?;
+        if let Some(rolling) = &metrics.rolling_corr {
+            writeln!(
+                out,
+                "Rolling {}-period correlation with {}: {}",
+                rolling.window,
+                bench,
+                fmt_opt(rolling.points.last().and_then(|p| p.value))
+            )?;
+        }
+    }
+    if let Some(rolling) = &metrics.rolling_sharpe {
+        writeln!(
+            out,
+            "Rolling {}-period Sharpe ratio: {}",
+            rolling.window,
+            fmt_opt(rolling.points.last().and_then(|p| p.value))
+        )?;
+    }
+    if metrics.benchmarks.len() > 1 {
+        let mut builder = Builder::default();
+        builder.push_record(["Benchmark", "Beta", "Correlation"]);
+        for b in &metrics.benchmarks {
+            builder.push_record([b.symbol.clone(), fmt_opt(b.beta), fmt_opt(b.correlation)]);
+        }
+        writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+    }
+
+    if let Some(er) = metrics.earnings_date {
+        writeln!(out, "Earnings date: {}", er.format("%Y-%m-%d %H:%M"))?;
+    }
+
+    if let Some(backtest) = &metrics.backtest {
+        print_backtest(out, backtest)?;
+    }
+    if let Some(impact) = &metrics.earnings_impact {
+        print_earnings_impact(out, impact)?;
+    }
+
+    if let Some(history) = &metrics.earnings_history {
+        print_earnings_history(out, history, ags)?;
+    }
+
+    if let Some(pos) = &metrics.position {
+        print_position(out, pos)?;
+    }
+
+    if let Some(season) = &metrics.seasonality {
+        print_seasonality(out, season)?;
+    }
+    Ok(())
+}
+
+// the --explain line for a value that was just printed
+fn explain(out: &mut dyn Write, ags: &Args, metric: Metric, value: Option<f64>) -> Result<()> {
+    match value.filter(|v| ags.explain && v.is_finite()) {
+        Some(v) => Ok(writeln!(out, "  -> {}", metric.explain(v))?),
+        None => Ok(()),
+    }
+}
+
+fn print_keltner(out: &mut dyn Write, k: &Keltner) -> Result<()> {
+    let label = format!(
+        "Keltner channel ({} EMA, {} ATR x {})",
+        k.ema_period, k.atr_period, k.multiplier
+    );
+    let (Some(lower), Some(middle), Some(upper), Some(close)) =
+        (k.lower, k.middle, k.upper, k.close)
+    else {
+        writeln!(out, "{}: n/a", label)?;
+        return Ok(());
+    };
+    writeln!(
+        out,
+        "{}: {:.2} / {:.2} / {:.2}",
+        label, lower, middle, upper
+    )?;
+    let position = if close > upper {
+        format!(
+            "{:.2}% above the upper band",
+            100.0 * (close - upper) / upper
+        )
+    } else if close < lower {
+        format!(
+            "{:.2}% below the lower band",
+            100.0 * (lower - close) / lower
+        )
+    } else {
+        // 0% sits on the lower band, 100% on the upper
+        format!(
+            "inside the channel, {:.0}% of the way from lower to upper",
+            100.0 * (close - lower) / (upper - lower)
+        )
+    };
+    writeln!(out, "Close {:.2} is {}", close, position)?;
+    Ok(())
+}
+
+fn print_seasonality(out: &mut dyn Write, season: &Seasonality) -> Result<()> {
+    let by = match season.basis {
+        SeasonalityBasis::Weekday => "weekday",
+        SeasonalityBasis::Month => "month",
+    };
+    writeln!(out, "\n--- Seasonality by {} ---", by)?;
+    if season.buckets.iter().all(|b| b.avg_return.is_none()) {
+        writeln!(
+            out,
+            "Not enough data: need at least {} returns per {}",
+            MIN_SEASON_SAMPLES, by
+        )?;
+        return Ok(());
+    }
+    let mut builder = Builder::default();
+    builder.push_record(["", "Count", "Avg %", "Win %"]);
+    for b in &season.buckets {
+        builder.push_record([
+            b.label.clone(),
+            b.count.to_string(),
+            b.avg_return
+                .map_or_else(|| "n/a".to_string(), |v| fmt_signed(v * 100.0)),
+            fmt_opt(b.win_rate.map(|v| v * 100.0)),
+        ]);
+    }
+    writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+    if !season.years.is_empty() {
+        print_season_heatmap(out, season)?;
+    }
+    Ok(())
+}
+
+/// Return % of every calendar month by year; blank where there is no data.
+fn print_season_heatmap(out: &mut dyn Write, season: &Seasonality) -> Result<()> {
+    let mut builder = Builder::default();
+    let mut header = vec![String::new()];
+    header.extend(MONTHS.iter().map(|m| m.to_string()));
+    builder.push_record(header);
+    for y in &season.years {
+        let mut record = vec![y.year.to_string()];
+        record.extend(
+            y.months
+                .iter()
+                .map(|r| r.map_or_else(String::new, |r| fmt_signed(r * 100.0))),
         );
-        if let Some(last) = fi.last {
-            let last = money_to_f64(&last);
-            if last < intraday.high {
-                println!(
-                    "Pct below intraday high for period: {:.2}",
-                    100.0 * (intraday.high - last) / intraday.high
-                )
-            }
-            if last < closing.high {
-                println!(
-                    "Pct below closing high for period: {:.2}",
-                    100.0 * (closing.high - last) / closing.high
-                )
-            }
+        builder.push_record(record);
+    }
+    writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+    Ok(())
+}
+
+fn print_position(out: &mut dyn Write, pos: &Position) -> Result<()> {
+    writeln!(out, "\n--- Position ---")?;
+    let per = if pos.shares.is_some() {
+        ""
+    } else {
+        " per share"
+    };
+    writeln!(
+        out,
+        "P&L: {:+.2}% ({}{}) vs cost basis {:.2}",
+        pos.pnl_pct,
+        fmt_dollars(pos.pnl),
+        per,
+        pos.cost_basis
+    )?;
+    if let (Some(date), Some(candle)) = (pos.buy_date, pos.buy_candle_date) {
+        writeln!(
+            out,
+            "Return since {} (candle of {}): {}%",
+            date,
+            candle,
+            fmt_opt(pos.since_buy)
+        )?;
+    }
+    Ok(())
+}
+
+fn fmt_dollars(value: f64) -> String {
+    if value < 0.0 {
+        format!("-${:.2}", -value)
+    } else {
+        format!("+${:.2}", value)
+    }
+}
+
+fn print_earnings_history(
+    out: &mut dyn Write,
+    history: &EarningsHistory,
+    ags: &Args,
+) -> Result<()> {
+    writeln!(out, "\n--- Earnings History ---")?;
+    if history.quarters.is_empty() && history.years.is_empty() {
+        writeln!(out, "no earnings history available")?;
+        return Ok(());
+    }
+    if !history.quarters.is_empty() {
+        let mut builder = Builder::default();
+        builder.push_record(["Quarter", "Reported EPS", "Estimated EPS", "Surprise %"]);
+        for q in &history.quarters {
+            builder.push_record([
+                q.period.clone(),
+                fmt_price(q.reported),
+                fmt_price(q.estimate),
+                q.surprise_pct.map_or_else(|| "n/a".to_string(), fmt_signed),
+            ]);
+        }
+        writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+    }
+    if !history.years.is_empty() {
+        let amount = |v: Option<f64>| v.map_or_else(|| "n/a".to_string(), |v| fmt_amount(v, ags));
+        let mut builder = Builder::default();
+        builder.push_record(["Year", "Revenue", "Earnings"]);
+        for y in &history.years {
+            builder.push_record([y.year.to_string(), amount(y.revenue), amount(y.earnings)]);
+        }
+        writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+    }
+    Ok(())
+}
+
+fn print_backtest(out: &mut dyn Write, bt: &Backtest) -> Result<()> {
+    writeln!(
+        out,
+        "\n--- SMA Crossover Backtest ({}/{}) ---",
+        bt.short, bt.long
+    )?;
+    let closed = bt.trades.iter().filter(|t| t.exit.is_some()).count();
+    writeln!(
+        out,
+        "Strategy: {:+.2}% over {} trades ({} still open), win rate {}",
+        bt.total_return,
+        bt.trades.len(),
+        bt.trades.len() - closed,
+        bt.win_rate
+            .map_or_else(|| "n/a".to_string(), |w| format!("{:.2}%", w)),
+    )?;
+    writeln!(out, "Max drawdown: {:.2}", bt.max_drawdown)?;
+    writeln!(
+        out,
+        "Buy and hold: {:+.2}%, max drawdown {:.2}",
+        bt.hold_return, bt.hold_max_drawdown
+    )?;
+    writeln!(
+        out,
+        "Fills at the next open after a cross, {:.2}% commission per fill",
+        bt.commission * 100.0
+    )?;
+    Ok(())
+}
+
+fn print_earnings_impact(out: &mut dyn Write, impact: &EarningsImpact) -> Result<()> {
+    writeln!(out, "\n--- Earnings Impact ---")?;
+    if impact.events.is_empty() {
+        writeln!(out, "No earnings dates within the period")?;
+        return Ok(());
+    }
+    let pct = |v: Option<f64>, reacted: bool| {
+        v.map_or_else(String::new, |v| {
+            fmt_signed(v * 100.0) + if reacted { "*" } else { "" }
+        })
+    };
+    let mut builder = Builder::default();
+    builder.push_record(["Earnings", "Trading Day", "Day %", "Next Day %"]);
+    for e in &impact.events {
+        builder.push_record([
+            e.earnings.format("%Y-%m-%d %H:%M").to_string(),
+            e.trading_day.date_naive().to_string(),
+            pct(e.day_return, e.reaction == Reaction::Day),
+            pct(e.next_day_return, e.reaction == Reaction::NextDay),
+        ]);
+    }
+    writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+    writeln!(
+        out,
+        "* the session each report moved: the report day before the close, the next day after it"
+    )?;
+    if let Some(avg) = impact.avg_abs_move {
+        writeln!(
+            out,
+            "Average absolute reaction move: {:.2}% ({} up, {} down)",
+            avg * 100.0,
+            impact.up,
+            impact.down
+        )?;
+    }
+    Ok(())
+}
+
+fn fmt_signed(value: f64) -> String {
+    // pad positives so they line up with negatives in a column
+    if value < 0.0 {
+        format!("{:.2}", value)
+    } else {
+        format!(" {:.2}", value)
+    }
+}
+
+/// Whole dollars, digit-grouped for the locale or abbreviated with --compact-numbers.
+fn fmt_amount(value: f64, ags: &Args) -> String {
+    if ags.compact_numbers {
+        fmt_compact_money(value, "$")
+    } else {
+        let sign = if value < 0.0 { "-" } else { "" };
+        let whole = value.abs().round() as u64;
+        format!("{}${}", sign, whole.to_formatted_string(&ags.locale))
+    }
+}
+
+/// A compact amount with the sign ahead of the currency symbol, e.g. -$2.50M.
+fn fmt_compact_money(value: f64, symbol: &str) -> String {
+    let sign = if value < 0.0 { "-" } else { "" };
+    format!("{}{}{}", sign, symbol, fmt_compact(value.abs()))
+}
+
+/// Shortens a large value with a K/M/B/T suffix, e.g. 1234567 -> 1.23M.
+fn fmt_compact(value: f64) -> String {
+    const SUFFIXES: [&str; 4] = ["K", "M", "B", "T"];
+    let sign = if value < 0.0 { "-" } else { "" };
+    let mut scaled = value.abs();
+    if scaled < 1000.0 {
+        return format!("{}{}", sign, scaled.round());
+    }
+    let mut idx = 0;
+    scaled /= 1000.0;
+    // move up a suffix if rounding would print 1000.0K
+    while idx + 1 < SUFFIXES.len() && (scaled * 10.0).round() >= 10_000.0 {
+        scaled /= 1000.0;
+        idx += 1;
+    }
+    if (scaled * 100.0).round() < 1000.0 {
+        format!("{}{:.2}{}", sign, scaled, SUFFIXES[idx])
+    } else {
+        format!("{}{:.1}{}", sign, scaled, SUFFIXES[idx])
+    }
+}
+
+fn fmt_price(value: Option<f64>) -> String {
+    value.map_or_else(|| "n/a".to_string(), |v| format!("{:.2}", v))
+}
+
+fn fmt_opt(value: Option<f64>) -> String {
+    value.map_or_else(|| "n/a".to_string(), |v| format!("{:.2}", v))
+}
+
+fn print_json_line(
+    out: &mut dyn Write,
+    symbol: &str,
+    analysis: &Result<Analysis>,
+    ags: &Args,
+) -> Result<()> {
+    let line = match analysis {
+        Ok(Analysis { report, metrics }) => JsonLine {
+            symbol: &report.symbol,
+            name: report.name.as_deref(),
+            metrics: Some(metrics),
+            candles: ags
+                .candles
+                .then(|| report.quotes.iter().map(candle_row).collect()),
+            error: None,
+        },
+        Err(e) => JsonLine {
+            symbol,
+            name: None,
+            metrics: None,
+            candles: None,
+            error: Some(format!("{:#}", e)),
+        },
+    };
+    // compact by default so each record stays on one line for piping
+    let json = if ags.json_pretty {
+        serde_json::to_string_pretty(&line)?
+    } else {
+        serde_json::to_string(&line)?
+    };
+    writeln!(out, "{}", json)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// A fetched report with its metrics, computed once for every output that needs them.
+fn analysis(report: Report, opts: &AnalyzeOptions, shared: &Shared) -> Analysis {
+    let metrics = calc_metrics(&report, opts, shared);
+    Analysis { report, metrics }
+}
+
+fn snapshot_entry(report: &Report, metrics: &Metrics) -> SnapshotEntry {
+    SnapshotEntry {
+        last_close: report.quotes.last().map(|q| money_to_f64(&q.close)),
+        pct_change: metrics.pct_change,
+        annualized_vol: metrics.annualized_vol,
+        sortino: metrics.sortino,
+        earnings_date: metrics.earnings_date,
+    }
+}
+
+fn summary_row(report: &Report, metrics: &Metrics) -> SummaryRow {
+    SummaryRow {
+        metrics: Some(SummaryMetrics {
+            last_close: report.quotes.last().map(|q| money_to_f64(&q.close)),
+            pct_change: metrics.pct_change,
+            annualized_vol: metrics.annualized_vol,
+            sharpe: metrics.sharpe,
+            max_drawdown: metrics.max_drawdown,
+            next_earnings: metrics.earnings_date,
+        }),
+        error: None,
+    }
+}
+
+fn write_batch_summary(
+    path: &Path,
+    tickers: &[String],
+    rows: &HashMap<String, SummaryRow>,
+) -> Result<()> {
+    let num =
+        |v: Option<f64>, places: usize| v.map_or_else(String::new, |v| format!("{:.*}", places, v));
+    let mut csv = String::from(
+        "symbol,last_close,pct_change,annualized_vol,sharpe,max_drawdown,next_earnings,error\n",
+    );
+    for symbol in tickers {
+        let symbol = symbol.to_uppercase();
+        let row = rows.get(&symbol);
+        let mut fields = vec![csv_field(&symbol)];
+        match row.and_then(|r| r.metrics.as_ref()) {
+            Some(m) => fields.extend([
+                num(m.last_close, 2),
+                num(m.pct_change, 4),
+                num(m.annualized_vol, 4),
+                num(m.sharpe, 4),
+                num(m.max_drawdown, 4),
+                m.next_earnings
+                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+            ]),
+            None => fields.extend(std::iter::repeat_n(String::new(), 6)),
         }
+        let error = row.and_then(|r| r.error.as_deref()).unwrap_or_default();
+        fields.push(csv_field(error));
+        csv += &fields.join(",");
+        csv.push('\n');
     }
+    std::fs::write(path, csv)
+        .with_context(|| format!("could not write batch summary {}", path.display()))?;
+    Ok(())
+}
 
-    if let Some(er) = earnings {
-        if !er.is_empty() {
-            println!("Earnings date: {}", er[0].format("%Y-%m-%d %H:%M"));
+// quotes a field containing a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn load_snapshot(path: &Path) -> Option<Snapshot> {
+    let data = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&data) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            eprintln!("Ignoring unreadable snapshot {}: {}", path.display(), e);
+            None
         }
     }
+}
 
-    print_cashflow(&cf);
+fn save_snapshot(path: &Path, snapshot: &Snapshot) -> Result<()> {
+    let data = serde_json::to_string_pretty(snapshot)?;
+    std::fs::write(path, data)
+        .with_context(|| format!("could not write snapshot {}", path.display()))?;
     Ok(())
 }
 
-fn display_plot(quotes: &[Candle]) {
+/// Changes since the previous snapshot, by ticker; None for a ticker it didn't have.
+#[derive(Debug, Serialize)]
+struct SnapshotChanges {
+    since: DateTime<Utc>,
+    tickers: BTreeMap<String, Option<Vec<String>>>,
+}
+
+fn snapshot_changes(
+    previous: &Snapshot,
+    current: &BTreeMap<String, SnapshotEntry>,
+) -> SnapshotChanges {
+    let tickers = current
+        .iter()
+        .map(|(symbol, cur)| {
+            let changes = previous
+                .tickers
+                .get(symbol)
+                .map(|prev| entry_changes(prev, cur));
+            (symbol.clone(), changes)
+        })
+        .collect();
+    SnapshotChanges {
+        since: previous.taken_at,
+        tickers,
+    }
+}
+
+fn print_snapshot_changes(out: &mut dyn Write, changes: &SnapshotChanges) -> Result<()> {
+    writeln!(
+        out,
+        "\n--- Changes since {} ---",
+        changes.since.format("%Y-%m-%d %H:%M")
+    )?;
+    for (symbol, changes) in &changes.tickers {
+        match changes {
+            None => writeln!(out, "{}: not in previous snapshot", symbol)?,
+            Some(changes) if changes.is_empty() => writeln!(out, "{}: no changes", symbol)?,
+            Some(changes) => writeln!(out, "{}: {}", symbol, changes.join("; "))?,
+        }
+    }
+    Ok(())
+}
+
+fn entry_changes(prev: &SnapshotEntry, cur: &SnapshotEntry) -> Vec<String> {
+    let mut changes = vec![];
+    let fields = [
+        ("last close", prev.last_close, cur.last_close),
+        ("pct change", prev.pct_change, cur.pct_change),
+        (
+            "annualized volatility",
+            prev.annualized_vol,
+            cur.annualized_vol,
+        ),
+        ("sortino ratio", prev.sortino, cur.sortino),
+    ];
+    for (label, before, after) in fields {
+        if let (Some(before), Some(after)) = (before, after) {
+            if format!("{:.2}", before) != format!("{:.2}", after) {
+                changes.push(format!(
+                    "{} changed from {:.2} to {:.2}",
+                    label, before, after
+                ));
+            }
+        }
+    }
+    if cur.earnings_date != prev.earnings_date {
+        if let Some(er) = cur.earnings_date {
+            changes.push(format!("new earnings date {}", er.format("%Y-%m-%d %H:%M")));
+        }
+    }
+    changes
+}
+
+fn candle_row(q: &Candle) -> CandleRow {
+    CandleRow {
+        ts: q.ts,
+        open: money_to_f64(&q.open),
+        high: money_to_f64(&q.high),
+        low: money_to_f64(&q.low),
+        close: money_to_f64(&q.close),
+        volume: q.volume,
+    }
+}
+
+fn display_plot(out: &mut dyn Write, quotes: &[Candle]) -> Result<()> {
     if quotes.is_empty() || quotes.len() < 2 {
-        return;
+        return Ok(());
     }
 
     let prices: Vec<(f32, f32)> = quotes
@@ -174,49 +2242,109 @@ fn display_plot(quotes: &[Candle]) {
         .map(|(_, y)| *y)
         .fold(f32::NEG_INFINITY, f32::max)
         * 1.01;
-    Chart::new_with_y_range(CHART_WIDTH, CHART_HEIGHT, 0.0, xmax, ymin, ymax)
-        .lineplot(&Shape::Steps(&prices))
-        .nice();
+    let shape = Shape::Steps(&prices);
+    let mut chart = Chart::new_with_y_range(CHART_WIDTH, CHART_HEIGHT, 0.0, xmax, ymin, ymax);
+    let chart = chart.lineplot(&shape);
+    chart.borders();
+    chart.axis();
+    chart.figures();
+    writeln!(out, "{}", chart)?;
+    Ok(())
 }
 
-fn print_quotes(quotes: &[Candle], returns: &[f64]) {
+fn print_quotes(
+    out: &mut dyn Write,
+    quotes: &[Candle],
+    returns: &[f64],
+    tz: Option<Tz>,
+    columns: &[Column],
+    start: usize,
+    ags: &Args,
+) -> Result<()> {
+    let interval: Interval = ags.candle_interval().into();
     if quotes.is_empty() {
-        println!("No quotes to display");
-        return;
+        writeln!(out, "No quotes to display")?;
+        return Ok(());
     }
 
+    let fmt_volume = |v: u64| {
+        if ags.compact_numbers {
+            fmt_compact(v as f64)
+        } else {
+            v.to_formatted_string(&ags.locale)
+        }
+    };
     let mut builder = Builder::default();
-    builder.push_record(["Date", "Volume", "Open", "High", "Low", "Close", "Return %"]);
-    for (idx, q) in quotes.iter().enumerate() {
+    let mut header = vec!["Date", "Volume", "Open", "High", "Low", "Close", "Return %"];
+    header.extend(columns.iter().map(|c| c.header.as_str()));
+    builder.push_record(header);
+    for (idx, q) in quotes.iter().enumerate().skip(start) {
         let mut ret_fmt = "".to_string();
         if idx > 0 {
-            let ret = returns[idx - 1] * 100.0;
-            if ret < 0.0 {
-                ret_fmt = format!("{:.2}", ret);
-            } else {
-                ret_fmt = format!(" {:.2}", ret);
-            }
+            ret_fmt = fmt_signed(returns[idx - 1] * 100.0);
         }
 
-        builder.push_record([
-            q.ts.date_naive().to_string(),
-            q.volume.unwrap().to_formatted_string(&Locale::en),
+        let date = if interval.is_intraday() {
+            let local = q.ts.with_timezone(&tz.unwrap_or(DEFAULT_TZ));
+            local.format("%Y-%m-%d %H:%M").to_string()
+        } else {
+            q.ts.date_naive().to_string()
+        };
+        let mut record = vec![
+            date,
+            q.volume.map(fmt_volume).unwrap_or_default(),
             format!("{:.2}", q.open.amount()),
             format!("{:.2}", q.high.amount()),
             format!("{:.2}", q.low.amount()),
             format!("{:.2}", q.close.amount()),
             ret_fmt,
-        ]);
+        ];
+        record.extend(columns.iter().map(|c| c.cells[idx].clone()));
+        builder.push_record(record);
     }
-    let table = builder.build().with(Style::sharp()).to_string();
-    println!("{}", table);
+
+    // a footer summarizing the displayed rows, under its own rule so it can't pass for a candle
+    let shown = &quotes[start..];
+    let (Some(first), Some(last)) = (shown.first(), shown.last()) else {
+        writeln!(out, "{}", builder.build().with(Style::sharp()))?;
+        return Ok(());
+    };
+    let high = shown
+        .iter()
+        .map(|q| money_to_f64(&q.high))
+        .fold(f64::MIN, f64::max);
+    let low = shown
+        .iter()
+        .map(|q| money_to_f64(&q.low))
+        .fold(f64::MAX, f64::min);
+    // compounds the Return % cells shown; the first row only has one when earlier rows are cut
+    let cumulative = returns[start.saturating_sub(1)..]
+        .iter()
+        .fold(1.0, |acc, r| acc * (1.0 + r));
+    let mut footer = vec![
+        "Period".to_string(),
+        fmt_volume(shown.iter().filter_map(|q| q.volume).sum()),
+        format!("{:.2}", first.open.amount()),
+        format!("{:.2}", high),
+        format!("{:.2}", low),
+        format!("{:.2}", last.close.amount()),
+        fmt_signed((cumulative - 1.0) * 100.0),
+    ];
+    footer.extend(columns.iter().map(|_| String::new()));
+    builder.push_record(footer);
+    let rule = HorizontalLine::full('─', '┼', '├', '┤');
+    let rows = 1 + shown.len();
+    let style = Style::sharp().horizontals([(1, rule), (rows, rule)]);
+    let table = builder.build().with(style).to_string();
+    writeln!(out, "{}", table)?;
+    Ok(())
 }
 
-fn print_cashflow(cf: &[CashflowRow]) {
+fn print_cashflow(out: &mut dyn Write, cf: &[CashflowRow], compact: bool) -> Result<()> {
     if cf.is_empty() {
-        return;
+        return Ok(());
     }
-    println!();
+    writeln!(out)?;
     let mut builder = Builder::default();
     builder.push_record(["Year End", "Free Cash Flow"]);
 
@@ -225,98 +2353,144 @@ fn print_cashflow(cf: &[CashflowRow]) {
         let fcf = &item.free_cash_flow;
         if let Some(period) = period {
             if let Some(fcf) = fcf {
-                builder.push_record([
-                    period.to_string(),
-                    fcf.to_localized_string().unwrap().to_string(),
-                ]);
+                let amount = if compact {
+                    let symbol = fcf.currency().symbol().unwrap_or_default();
+                    fmt_compact_money(money_to_f64(fcf), &symbol)
+                } else {
+                    fcf.to_localized_string().unwrap().to_string()
+                };
+                builder.push_record([period.to_string(), amount]);
             }
         }
     }
 
     let table = builder.build().with(Style::sharp()).to_string();
-    println!("{}", table);
+    writeln!(out, "{}", table)?;
+    Ok(())
 }
 
-async fn get_quotes(ticker: &Ticker, range: Range) -> Result<Vec<Candle>> {
-    let hist = ticker
-        .history(Some(range), Some(Interval::D1), false)
-        .await?;
-    Ok(hist)
+fn session_summary(prepost: bool, regular_hours_only: bool) -> &'static str {
+    match (prepost, regular_hours_only) {
+        (false, _) => "regular hours only",
+        (true, false) => "pre-market, regular, post-market",
+        (true, true) => "pre-market, regular, post-market (stats use regular hours only)",
+    }
 }
 
-async fn get_earnings_dates(ticker: &Ticker) -> Result<Vec<DateTime<Utc>>> {
-    let cal = ticker.calendar().await?;
-    let earnings = cal.earnings_dates;
-    Ok(earnings)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn calc_returns(quotes: &[Candle]) -> Vec<f64> {
-    let mut res: Vec<f64> = vec![];
-    for i in 1..quotes.len() {
-        let cur = money_to_f64(&quotes[i].close);
-        let prev = money_to_f64(&quotes[i - 1].close);
-        res.push((cur - prev) / prev);
-    }
-    res
-}
+    #[test]
+    fn quote_options_from_args() {
+        let ags = Args::parse_from([
+            "vol",
+            "-t",
+            "msft",
+            "-r",
+            "d5",
+            "-i",
+            "i5m",
+            "--include-prepost",
+        ]);
+        let quotes = QuoteOptions::from(&ags);
+        assert_eq!(quotes.range, Range::D5);
+        assert_eq!(quotes.interval, Interval::I5m);
+        assert!(quotes.prepost);
+        assert!(quotes.auto_adjust);
+        assert!(quotes.include_actions);
+        assert_eq!(AnalyzeOptions::from(&ags).quotes, quotes);
 
-fn get_price_range(quotes: &[Candle]) -> Option<(PriceRange, PriceRange)> {
-    // get intraday and closing price ranges over time period
-    if quotes.is_empty() {
-        return None;
+        // extended hours only apply to intraday candles
+        let ags = Args::parse_from(["vol", "-t", "msft", "--include-prepost"]);
+        assert!(!QuoteOptions::from(&ags).prepost);
     }
 
-    let mut intraday = PriceRange {
-        low: f64::INFINITY,
-        high: f64::NEG_INFINITY,
-    };
-    let mut closing = PriceRange {
-        low: f64::INFINITY,
-        high: f64::NEG_INFINITY,
-    };
-    for q in quotes {
-        let low = money_to_f64(&q.low);
-        let high = money_to_f64(&q.high);
-        let close = money_to_f64(&q.close);
-        intraday.low = intraday.low.min(low);
-        intraday.high = intraday.high.max(high);
-        closing.low = closing.low.min(close);
-        closing.high = closing.high.max(close);
+    #[test]
+    fn every_listed_metric_has_its_arg() {
+        let cmd = Args::command();
+        for (name, source) in catalog::METRICS {
+            if let catalog::Source::Flag(id) = source {
+                assert!(
+                    cmd.get_arguments().any(|a| a.get_id() == id),
+                    "{name}: no arg {id}"
+                );
+            }
+        }
+        // and every arg is either listed or deliberately left out: these pick what is fetched,
+        // shape the output, or tune a listed metric without adding one
+        let other_args = [
+            "ticker",
+            "range",
+            "interval",
+            "include_prepost",
+            "regular_hours_only",
+            "format",
+            "candles",
+            "json_pretty",
+            "concurrency",
+            "calendar_annualization",
+            "rank",
+            "top",
+            "max_candles",
+            "stats_window",
+            "snapshot",
+            "batch_summary",
+            "output",
+            "tee",
+            "price_type",
+            "timing",
+            "min_volume",
+            "locale",
+            "compact_numbers",
+            "shares",
+            "buy_date",
+            "risk_free_ticker",
+            "risk_free_rate",
+            "fixtures",
+            "fail_fast",
+            "explain",
+            "commission",
+            "list_metrics",
+        ];
+        for arg in cmd.get_arguments() {
+            let id = arg.get_id().as_str();
+            let listed = catalog::METRICS
+                .iter()
+                .any(|(_, source)| matches!(source, catalog::Source::Flag(f) if *f == id));
+            assert!(
+                listed != other_args.contains(&id),
+                "--{id} should be in exactly one of catalog::METRICS and other_args"
+            );
+        }
+        let mut out = vec![];
+        catalog::print(&mut out, &cmd).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("│ Money Flow Index       │ --mfi "));
     }
 
-    Some((intraday, closing))
-}
-
-async fn get_risk_free_rate(client: &yfinance_rs::YfClient) -> Result<f64> {
-    // 13 WEEK TREASURY BILL: ^IRX
-    let ticker = Ticker::new(client, "^IRX");
-    let fi = ticker.fast_info().await?;
-    let last = fi
-        .last
-        .ok_or_else(|| anyhow::anyhow!("Could not retrieve ^IRX price"))?;
-    let rate = money_to_f64(&last) / 100.0;
-    Ok(rate)
-}
-
-fn sortino_ratio(returns: &[f64], risk_free_annual: f64) -> f64 {
-    if returns.is_empty() {
-        return 0.0;
+    #[test]
+    fn auto_interval_follows_the_range() {
+        assert_eq!(auto_interval(RangeArg::D5), CandleInterval::I30m);
+        assert_eq!(auto_interval(RangeArg::Y2), CandleInterval::D1);
+        assert_eq!(auto_interval(RangeArg::Y10), CandleInterval::W1);
+        assert_eq!(auto_interval(RangeArg::Max), CandleInterval::M1);
+        // only auto depends on the range
+        let ags = Args::parse_from(["vol", "-t", "x", "-r", "y10", "-i", "auto"]);
+        assert_eq!(ags.candle_interval(), CandleInterval::W1);
+        let ags = Args::parse_from(["vol", "-t", "x", "-r", "y10", "-i", "d1"]);
+        assert_eq!(ags.candle_interval(), CandleInterval::D1);
     }
 
-    let risk_free_daily = (1.0 + risk_free_annual).powf(1.0 / TRADING_DAYS_YEAR) - 1.0;
-    let excess_returns: Vec<f64> = returns.iter().map(|r| r - risk_free_daily).collect();
-    let mean_excess = excess_returns.as_slice().mean();
-    let downside_variance = excess_returns
-        .iter()
-        .map(|r| if *r < 0.0 { r * r } else { 0.0 })
-        .sum::<f64>()
-        / excess_returns.len() as f64;
-
-    let downside_std_dev = downside_variance.sqrt();
-    if downside_std_dev < f64::EPSILON {
-        return 0.0;
+    #[test]
+    fn compact_numbers() {
+        assert_eq!(fmt_compact(950.0), "950");
+        assert_eq!(fmt_compact(12_345.0), "12.3K");
+        assert_eq!(fmt_compact(456_700_000.0), "456.7M");
+        assert_eq!(fmt_compact(1_234_000_000.0), "1.23B");
+        assert_eq!(fmt_compact(999_960.0), "1.00M");
+        assert_eq!(fmt_compact(-2_500_000.0), "-2.50M");
+        assert_eq!(fmt_compact_money(-2_500_000.0, "$"), "-$2.50M");
+        assert_eq!(fmt_compact_money(12_345.0, "€"), "€12.3K");
     }
-
-    let annualization_factor = TRADING_DAYS_YEAR.sqrt();
-    (mean_excess * TRADING_DAYS_YEAR) / (downside_std_dev * annualization_factor)
 }
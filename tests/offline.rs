@@ -0,0 +1,768 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use vol::{
+    analyze, calc_backtest, calc_cmo, calc_dollar_volume, calc_drawdown_duration, calc_mfi,
+    calc_relative_volume, close_vol, earnings_candle, garman_klass_vol, parkinson_vol, sector_etf,
+    AnalyzeOptions, Exchange, PriceType, QuoteOptions, RateSource, VolEstimator, DEFAULT_TZ,
+};
+use yfinance_rs::{Candle, Interval, YfClient};
+
+// six daily candles closing at 100, 102, 101, 104, 103 and 106, with ^IRX at 5%
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-6,
+        "expected {expected}, got {actual}"
+    );
+}
+
+fn vol(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_vol"))
+        .args(["--fixtures", fixtures().to_str().unwrap()])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn run(args: &[&str]) -> String {
+    let out = vol(args);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[tokio::test]
+async fn computes_returns_and_volatility() {
+    let opts = AnalyzeOptions {
+        fixtures: Some(fixtures()),
+        ..Default::default()
+    };
+    let analysis = analyze(&YfClient::default(), "test", opts).await.unwrap();
+    let report = &analysis.report;
+    assert_eq!(report.symbol, "TEST");
+    assert_eq!(report.quotes.len(), 6);
+    let expected = [0.02, -1.0 / 102.0, 3.0 / 101.0, -1.0 / 104.0, 3.0 / 103.0];
+    assert_eq!(report.returns.len(), expected.len());
+    for (actual, expected) in report.returns.iter().zip(expected) {
+        assert_close(*actual, expected);
+    }
+
+    let metrics = &analysis.metrics;
+    assert_close(metrics.pct_change.unwrap(), 6.0);
+    assert_close(metrics.std_dev.unwrap(), 0.020082757208122668);
+    assert_close(metrics.annualized_vol.unwrap(), 31.880388727909448);
+    assert_close(metrics.risk_free_rate, 0.05);
+    assert_eq!(
+        metrics.risk_free_source,
+        RateSource::Ticker("^IRX".to_string())
+    );
+}
+
+#[tokio::test]
+async fn missing_fixture_is_an_error() {
+    let opts = AnalyzeOptions {
+        fixtures: Some(fixtures()),
+        ..Default::default()
+    };
+    let err = analyze(&YfClient::default(), "nope", opts)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("NOPE.json"), "{err}");
+}
+
+#[test]
+fn jsonl_errors_keep_their_context() {
+    // the same chain as the table mode failure summary, not just the outermost message
+    let out = vol(&["-t", "nope", "-f", "jsonl"]);
+    let out = String::from_utf8(out.stdout).unwrap();
+    assert!(out.contains("\"error\":\"could not read fixture "));
+    assert!(out.contains("NOPE.json: No such file or directory"));
+}
+
+#[test]
+fn renders_table_and_analysis() {
+    let out = run(&["-t", "test"]);
+    assert!(out.contains("│ Date       │ Volume │ Open   │ High   │ Low    │ Close  │ Return % │"));
+    assert!(out.contains("│ 2024-01-02 │ 1,000  │ 99.00  │ 101.00 │ 98.00  │ 100.00 │          │"));
+    assert!(out.contains("│ 2024-01-09 │ 6,000  │ 103.00 │ 107.00 │ 102.00 │ 106.00 │  2.91    │"));
+    assert!(out.contains("Pct change over period: 6.00"));
+    assert!(out.contains("Annualized volatility: 31.88"));
+    assert!(out.contains("using risk free rate of 5.00% from ^IRX"));
+}
+
+#[test]
+fn keeps_going_past_failed_tickers() {
+    let out = vol(&["-t", "nope,test"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Pct change over period: 6.00"));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Failed 1 of 2 tickers"), "{stderr}");
+    assert!(stderr.contains("NOPE: could not read fixture"), "{stderr}");
+}
+
+#[test]
+fn fails_when_every_ticker_fails() {
+    let out = vol(&["-t", "nope,gone"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("every ticker failed"));
+}
+
+#[test]
+fn fail_fast_stops_at_the_first_failure() {
+    let out = vol(&["-t", "nope,test", "--fail-fast"]);
+    assert!(!out.status.success());
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("Pct change"));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("could not fetch NOPE"));
+}
+
+#[test]
+fn vwap_bands_flag_closes_outside_the_bands() {
+    // four 5-minute candles at 100, 102, 101 and 110 with volumes 100, 100, 200 and 100
+    let out = run(&["-t", "intra", "-i", "i5m", "--vwap-bands", "1"]);
+    assert!(out.contains("│ 2024-01-02 09:45 │ 100    │ 110.00 │ 110.00 │ 110.00 │ 110.00 │  8.91    │ 102.80 │ 106.46 │ 99.14  │ above │"));
+    assert!(out.contains("VWAP: 102.80 (±1σ bands pierced by 1 closes above, 0 below)"));
+}
+
+#[test]
+fn vwap_bands_are_ignored_for_daily_candles() {
+    let out = vol(&["-t", "test", "--vwap-bands", "2"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("only applies to intraday intervals"));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("VWAP"));
+}
+
+#[test]
+fn us_holidays() {
+    let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+    let expected = [
+        date(1, 1),
+        date(1, 15),
+        date(2, 19),
+        date(3, 29),
+        date(5, 27),
+        date(6, 19),
+        date(7, 4),
+        date(9, 2),
+        date(11, 28),
+        date(12, 25),
+    ];
+    assert_eq!(Exchange::Us.holidays(2024), expected);
+    // Saturday July 4th is observed on the Friday; a Saturday New Year isn't observed at all
+    let holidays = Exchange::Us.holidays(2026);
+    assert!(holidays.contains(&NaiveDate::from_ymd_opt(2026, 7, 3).unwrap()));
+    assert!(!Exchange::Us
+        .holidays(2022)
+        .contains(&NaiveDate::from_ymd_opt(2021, 12, 31).unwrap()));
+}
+
+#[test]
+fn us_closures_and_older_rules() {
+    let closed = |y, m, d| {
+        let date = NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        Exchange::Us.holidays(y).contains(&date)
+    };
+    // one-off closures
+    assert!(closed(2025, 1, 9));
+    assert!(closed(2018, 12, 5));
+    assert!(closed(2012, 10, 29) && closed(2012, 10, 30));
+    assert!((11..=14).all(|d| closed(2001, 9, d)));
+    assert!(closed(2007, 1, 2));
+    assert!(!closed(2001, 9, 10) && !closed(2001, 9, 17));
+    // Martin Luther King Jr. Day from 1998
+    assert!(closed(1998, 1, 19));
+    assert!(!closed(1997, 1, 20));
+    // fixed-date Washington's Birthday and Memorial Day, and Election Day, before 1971
+    assert!(closed(1970, 2, 23) && !closed(1970, 2, 16));
+    assert!(closed(1970, 5, 29) && !closed(1970, 5, 25));
+    assert!(closed(1968, 11, 5));
+    assert!(!closed(1970, 11, 3));
+    assert!(closed(1980, 11, 4));
+    assert!(!closed(1984, 11, 6));
+    let days = Exchange::Us.trading_days(
+        NaiveDate::from_ymd_opt(2001, 9, 10).unwrap(),
+        NaiveDate::from_ymd_opt(2001, 9, 17).unwrap(),
+    );
+    assert_eq!(days.len(), 2);
+}
+
+#[test]
+fn coverage_warning_skips_market_holidays() {
+    // candles on Friday 2024-01-12 and Tuesday 2024-01-16, around Martin Luther King Jr. Day
+    let out = run(&["-t", "holiday"]);
+    assert!(!out.contains("missing data"));
+    let out = run(&["-t", "holiday", "--exchange", "weekdays"]);
+    assert!(out.contains("Warning: missing data for 1 of 3 expected trading days (2024-01-15)"));
+}
+
+#[test]
+fn drawdown_column_marks_the_deepest_row() {
+    let out = run(&["-t", "test", "--show-drawdown"]);
+    assert!(out.contains(
+        "│ 2024-01-04 │ 3,000  │ 102.00 │ 103.00 │ 100.00 │ 101.00 │ -0.98    │ 0.98 max │"
+    ));
+    assert!(out.contains(
+        "│ 2024-01-08 │ 5,000  │ 104.00 │ 105.00 │ 102.00 │ 103.00 │ -0.96    │ 0.96     │"
+    ));
+}
+
+#[test]
+fn keltner_and_bollinger_columns_flag_squeezes() {
+    let out = run(&["-t", "test", "--keltner", "2,1", "--bollinger", "3,0.5"]);
+    // the EMA is defined from the second candle, the ATR and both bands from the third
+    assert!(out.contains("│ 2024-01-03 │ 2,000  │ 100.00 │ 103.00 │ 99.00  │ 102.00 │  2.00    │        │ 100.50 │         │        │         │         │"));
+    assert!(out.contains("│ 2024-01-04 │ 3,000  │ 102.00 │ 103.00 │ 100.00 │ 101.00 │ -0.98    │ 97.56  │ 101.06 │ 104.56  │ 100.50 │ 101.50  │ yes     │"));
+    assert!(out.contains("Keltner channel (2 EMA, 2 ATR x 1): 100.02 / 104.34 / 108.65"));
+    assert!(out.contains("Squeeze (Bollinger inside Keltner): 4 candles, active now"));
+}
+
+#[test]
+fn band_flags_need_a_period_and_multiplier() {
+    let out = vol(&["-t", "test", "--keltner", "20"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("expected PERIOD,MULT"));
+}
+
+#[tokio::test]
+async fn dollar_volume_skips_candles_without_volume() {
+    let opts = AnalyzeOptions {
+        fixtures: Some(fixtures()),
+        ..Default::default()
+    };
+    let analysis = analyze(&YfClient::default(), "test", opts).await.unwrap();
+    let dv = analysis.metrics.dollar_volume.unwrap();
+    assert_close(dv.average, 2_174_000.0 / 6.0);
+    assert_close(dv.median, 359_500.0);
+    assert_eq!(dv.skipped, 0);
+
+    let mut quotes = analysis.report.quotes.clone();
+    quotes[0].volume = None;
+    let dv = calc_dollar_volume(&quotes).unwrap();
+    assert_close(dv.average, 2_074_000.0 / 5.0);
+    assert_close(dv.median, 416_000.0);
+    assert_eq!(dv.skipped, 1);
+
+    let out = run(&["-t", "test"]);
+    assert!(out.contains("avg daily $ volume: $362,333 (median $359,500)"));
+}
+
+#[test]
+fn maps_sectors_to_etfs() {
+    assert_eq!(sector_etf(Some("Technology")), "XLK");
+    assert_eq!(sector_etf(Some("financial services")), "XLF");
+    assert_eq!(sector_etf(Some("Energy")), "XLE");
+    assert_eq!(sector_etf(Some("Shell Companies")), "SPY");
+    assert_eq!(sector_etf(None), "SPY");
+}
+
+#[test]
+fn sector_compare_falls_back_to_spy() {
+    // fixtures have no profile, so the sector is unknown; SPY.json mirrors TEST.json
+    let out = run(&["-t", "test", "--sector-compare"]);
+    assert!(out.contains("Sector: unknown (compared against SPY)"));
+    assert!(out.contains("Correlation with SPY: 1.00"));
+}
+
+#[test]
+fn earnings_history_without_data() {
+    let out = run(&["-t", "test", "--earnings-history"]);
+    assert!(out.contains("--- Earnings History ---\nno earnings history available"));
+}
+
+// one daily candle per (open, high, low, close)
+fn synthetic(ohlc: &[(f64, f64, f64, f64)]) -> Vec<Candle> {
+    let money = |v: &f64| serde_json::json!({ "amount": v.to_string(), "currency": "USD" });
+    ohlc.iter()
+        .enumerate()
+        .map(|(i, (o, h, l, c))| {
+            serde_json::from_value(serde_json::json!({
+                "ts": 1704205800 + i as i64 * 86400,
+                "open": money(o),
+                "high": money(h),
+                "low": money(l),
+                "close": money(c),
+                "volume": 1000,
+            }))
+            .unwrap()
+        })
+        .collect()
+}
+
+// daily candles opening at the previous close, with the high and low at the body
+fn from_closes(closes: &[f64]) -> Vec<Candle> {
+    let ohlc: Vec<_> = closes
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let o = closes[i.saturating_sub(1)];
+            (o, o.max(c), o.min(c), c)
+        })
+        .collect();
+    synthetic(&ohlc)
+}
+
+#[test]
+fn range_based_volatility_estimators() {
+    let range = (102.0_f64 / 100.0).ln();
+    // opens equal closes, so Garman-Klass sees only the range term
+    let flat = synthetic(&[(101.0, 102.0, 100.0, 101.0); 10]);
+    let parkinson = parkinson_vol(&flat, 252.0).unwrap();
+    let gk = garman_klass_vol(&flat, 252.0).unwrap();
+    assert_close(
+        parkinson,
+        range / (4.0 * std::f64::consts::LN_2).sqrt() * 252.0_f64.sqrt() * 100.0,
+    );
+    assert_close(gk, range * 0.5_f64.sqrt() * 252.0_f64.sqrt() * 100.0);
+    assert_close(gk / parkinson, (2.0 * std::f64::consts::LN_2).sqrt());
+
+    // the same range with an open-to-close move lowers Garman-Klass but not Parkinson
+    let trending = synthetic(&[(100.5, 102.0, 100.0, 101.5); 10]);
+    assert_close(parkinson_vol(&trending, 252.0).unwrap(), parkinson);
+    assert!(garman_klass_vol(&trending, 252.0).unwrap() < gk);
+
+    // no range means no volatility
+    let still = synthetic(&[(100.0, 100.0, 100.0, 100.0); 5]);
+    assert_close(parkinson_vol(&still, 252.0).unwrap(), 0.0);
+    assert_close(garman_klass_vol(&still, 252.0).unwrap(), 0.0);
+    assert_eq!(parkinson_vol(&[], 252.0), None);
+    assert_close(
+        close_vol(&[0.01, -0.01, 0.01, -0.01], 252.0).unwrap(),
+        0.02 / 3.0_f64.sqrt() * 252.0_f64.sqrt() * 100.0,
+    );
+}
+
+#[test]
+fn prints_selected_vol_estimators() {
+    let out = run(&["-t", "test", "--vol-estimator", "parkinson"]);
+    assert!(out.contains("Annualized volatility (Parkinson): "));
+    assert!(!out.contains("Annualized volatility: "));
+    assert!(!out.contains("Garman-Klass"));
+
+    let out = run(&["-t", "test", "--vol-estimator", "all"]);
+    assert!(out.contains("Annualized volatility: 31.88"));
+    assert!(out.contains("Annualized volatility (Parkinson): "));
+    assert!(out.contains("Annualized volatility (Garman-Klass): "));
+}
+
+#[test]
+fn chande_momentum() {
+    let candles = |closes: &[f64]| {
+        let ohlc: Vec<_> = closes.iter().map(|&c| (c, c, c, c)).collect();
+        synthetic(&ohlc)
+    };
+    // changes +2, -1, +3, -1, +3: up 8, down 2
+    let quotes = candles(&[100.0, 102.0, 101.0, 104.0, 103.0, 106.0]);
+    assert_close(calc_cmo(&quotes, 5, PriceType::Close).unwrap(), 60.0);
+    // only the last two changes, -1 and +3
+    assert_close(calc_cmo(&quotes, 2, PriceType::Close).unwrap(), 50.0);
+    assert_eq!(calc_cmo(&quotes, 6, PriceType::Close), None);
+    assert_close(
+        calc_cmo(&candles(&[100.0; 4]), 3, PriceType::Close).unwrap(),
+        0.0,
+    );
+    assert_close(
+        calc_cmo(&candles(&[103.0, 102.0, 101.0]), 2, PriceType::Close).unwrap(),
+        -100.0,
+    );
+
+    let out = run(&["-t", "test", "--cmo", "5"]);
+    assert!(out.contains("Chande momentum (5): 60.00 (overbought)"));
+}
+
+#[test]
+fn price_types() {
+    let q = &synthetic(&[(100.0, 106.0, 98.0, 104.0)])[0];
+    assert_close(PriceType::Close.of(q), 104.0);
+    assert_close(PriceType::Typical.of(q), 308.0 / 3.0);
+    assert_close(PriceType::Median.of(q), 102.0);
+    assert_close(PriceType::Hlc.of(q), 103.0);
+    assert_close(PriceType::Ohlc4.of(q), 102.0);
+
+    // the fixture's (high + low) / 2 rises every day even though the closes don't
+    let out = run(&["-t", "test", "--cmo", "5", "--price-type", "median"]);
+    assert!(out.contains("Chande momentum (5): 100.00 (overbought)"));
+}
+
+#[test]
+fn notes_metrics_skipped_for_thin_data() {
+    // SHORT.json is the first two TEST.json candles
+    let out = run(&["-t", "short"]);
+    assert!(
+        out.contains("Note: volatility/Sortino/Sharpe/Omega skipped: need ≥3 candles, have 2\n")
+    );
+    assert!(out.contains("Pct change over period: 2.00"));
+    assert!(!out.contains("Annualized volatility"));
+    assert!(!run(&["-t", "test"]).contains("Note: "));
+    // window and period checks go into the same note
+    let out = run(&[
+        "-t",
+        "test",
+        "--mfi",
+        "--cmo",
+        "5",
+        "--rolling-sharpe",
+        "6",
+        "--relative-volume",
+        "20",
+        "-b",
+        "spy",
+        "--rolling-corr",
+        "10",
+    ]);
+    assert!(out.contains(
+        "Note: rolling Sharpe skipped: need ≥7 candles, have 6; \
+         MFI skipped: need ≥15 candles, have 6; \
+         relative volume skipped: need ≥21 candles, have 6; \
+         rolling correlation skipped: need ≥11 candles, have 6\n"
+    ));
+    assert!(out.contains("Chande momentum"));
+}
+
+#[test]
+fn batch_summary_has_a_row_per_ticker() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("batch-summary.csv");
+    let out = vol(&["-t", "test,nope", "--batch-summary", path.to_str().unwrap()]);
+    assert!(out.status.success());
+    let csv = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "symbol,last_close,pct_change,annualized_vol,sharpe,max_drawdown,next_earnings,error"
+    );
+    assert!(lines[1].starts_with("TEST,106.00,6.0000,31.8804,"));
+    assert!(lines[1].ends_with(",,"));
+    assert!(lines[2].starts_with("NOPE,,,,,,,"));
+    assert!(lines[2].contains("could not read fixture"));
+    assert_eq!(lines.len(), 3);
+}
+
+#[test]
+fn monthly_seasonality_across_years() {
+    // month-end closes from Dec 2018 to Dec 2023, up 10% every November and down 5% every October
+    let out = run(&[
+        "-t",
+        "monthly",
+        "-r",
+        "y5",
+        "-i",
+        "m1",
+        "--seasonality",
+        "month",
+    ]);
+    assert!(out.contains("│ Nov │ 5     │  10.00 │ 100.00 │"));
+    assert!(out.contains("│ Oct │ 5     │ -5.00  │ 0.00   │"));
+    // Dec 2018 has no earlier month to compare with, so the heatmap starts in 2019
+    assert!(out.contains("│ 2019 │  0.00 │"));
+    assert!(!out.contains("│ 2018 │"));
+
+    // a single month of daily candles has no month-over-month return at all
+    let out = vol(&["-t", "test", "--seasonality", "month"]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("Not enough data: need at least 4 returns per month"));
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .contains("--seasonality by month compares several years"));
+}
+
+#[test]
+fn anchored_vwap() {
+    let out = run(&["-t", "test", "--anchored-vwap", "2024-01-04"]);
+    // typical prices 101.33, 103, 103.33 and 105 on volumes of 3000 to 6000
+    assert!(out.contains("Anchored VWAP from 2024-01-04: 103.48 (close 106.00, +2.43%)"));
+    // a weekend anchor starts at the next trading day
+    let out = run(&["-t", "test", "--anchored-vwap", "2024-01-06"]);
+    assert!(out.contains("Anchored VWAP from 2024-01-08: "));
+
+    let out = vol(&["-t", "test", "--anchored-vwap", "2024-02-01", "--fail-fast"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .contains("anchor date 2024-02-01 for TEST is outside the fetched window"));
+}
+
+#[tokio::test]
+async fn parallel_indicators_match_sequential() {
+    let opts = |parallel_threshold| AnalyzeOptions {
+        fixtures: Some(fixtures()),
+        quotes: QuoteOptions {
+            // intraday so the VWAP bands are computed too
+            interval: Interval::I5m,
+            ..Default::default()
+        },
+        vol_estimator: VolEstimator::All,
+        mfi: Some(14),
+        cmo: Some(14),
+        keltner: Some((20, 10, 2.0)),
+        bollinger: Some((20, 2.0)),
+        vwap_bands: Some(2.0),
+        parallel_threshold,
+        ..Default::default()
+    };
+    let client = YfClient::default();
+    let sequential = analyze(&client, "monthly", opts(usize::MAX)).await.unwrap();
+    let parallel = analyze(&client, "monthly", opts(0)).await.unwrap();
+    let sequential = serde_json::to_string(&sequential.metrics).unwrap();
+    assert_eq!(
+        serde_json::to_string(&parallel.metrics).unwrap(),
+        sequential
+    );
+    for key in [
+        "parkinson_vol",
+        "garman_klass_vol",
+        "mfi",
+        "cmo",
+        "keltner",
+        "bollinger",
+        "squeeze",
+        "vwap_bands",
+    ] {
+        assert!(sequential.contains(&format!("\"{key}\":")), "{key} missing");
+    }
+}
+
+#[test]
+fn explain_reads_each_headline_metric() {
+    let out = run(&["-t", "test", "--explain"]);
+    assert!(out.contains("Annualized volatility: 31.88\n  -> high; this stock is quite volatile"));
+    assert!(!run(&["-t", "test"]).contains("  -> "));
+}
+
+#[test]
+fn rebase_starts_every_ticker_at_100_on_the_latest_common_date() {
+    let out = run(&["-t", "test,late", "--rebase"]);
+    assert!(out.contains("--- Rebased to 100 on 2024-01-04 ---"));
+    assert!(out.contains("│ 2024-01-04 │ 100.00 │ 100.00 │"));
+    assert!(out.contains("TEST ended at 104.95 (+4.95%)"));
+    assert!(out.contains("LATE ended at 110.00 (+10.00%)"));
+}
+
+#[test]
+fn quote_table_ends_with_a_period_footer() {
+    let out = run(&["-t", "test", "--max-candles", "3"]);
+    assert!(out.contains(concat!(
+        "├────────────┼────────┼────────┼────────┼────────┼────────┼──────────┤\n",
+        "│ Period     │ 15,000 │ 101.00 │ 107.00 │ 100.00 │ 106.00 │  4.95    │\n",
+        "└",
+    )));
+    // exports stay candle rows only
+    let out = run(&["-t", "test", "-f", "jsonl", "--candles"]);
+    assert!(!out.contains("Period") && !out.contains("21000"));
+}
+
+#[test]
+fn ranking_tables_do_not_overlap() {
+    let out = run(&["-t", "test,late,spy", "--rank", "--top", "2"]);
+    let (top, bottom) = out.split_once("Bottom 1 by Return %").unwrap();
+    assert!(top.starts_with("Top 2 by Return %"));
+    assert!(top.contains("LATE") && top.contains("TEST") && !top.contains("SPY"));
+    assert!(bottom.contains("SPY") && !bottom.contains("TEST"));
+    // nothing is left for a bottom table
+    let out = run(&["-t", "test,late", "--rank", "--top", "2"]);
+    assert!(!out.contains("Bottom"));
+    assert!(!vol(&["-t", "test", "--rank", "--top", "0"])
+        .status
+        .success());
+}
+
+#[test]
+fn max_candles_must_be_positive() {
+    let out = vol(&["-t", "test", "--max-candles", "0"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--max-candles"));
+}
+
+#[test]
+fn buy_date_uses_its_own_candle_or_the_next() {
+    let out = run(&[
+        "-t",
+        "test",
+        "--cost-basis",
+        "100",
+        "--buy-date",
+        "2024-01-05",
+    ]);
+    assert!(out.contains("Return since 2024-01-05 (candle of 2024-01-05): 1.92%"));
+    // a Saturday buys into Monday's candle
+    let out = run(&[
+        "-t",
+        "test",
+        "--cost-basis",
+        "100",
+        "--buy-date",
+        "2024-01-06",
+    ]);
+    assert!(out.contains("Return since 2024-01-06 (candle of 2024-01-08): 2.91%"));
+}
+
+#[test]
+fn snapshot_changes_in_every_output_mode() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("modes-snapshot.json");
+    let _ = std::fs::remove_file(&path);
+    let snapshot = path.to_str().unwrap();
+    run(&["-t", "test", "--snapshot", snapshot]);
+
+    let out = run(&["-t", "test,late", "--rank", "--snapshot", snapshot]);
+    assert!(out.contains("LATE: not in previous snapshot\nTEST: no changes"));
+    let out = run(&["-t", "test,late", "-f", "jsonl", "--snapshot", snapshot]);
+    let last: serde_json::Value = serde_json::from_str(out.lines().last().unwrap()).unwrap();
+    let tickers = &last["snapshot_changes"]["tickers"];
+    assert_eq!(tickers["TEST"], serde_json::json!([]));
+    assert_eq!(tickers["LATE"], serde_json::json!([]));
+}
+
+#[test]
+fn drawdown_duration_runs_from_peak_to_recovery() {
+    // peak on the 2nd candle, three closes below it, back above on the 6th, then lower again
+    let quotes = from_closes(&[100.0, 110.0, 90.0, 95.0, 105.0, 111.0, 108.0]);
+    let dur = calc_drawdown_duration(&quotes).unwrap();
+    assert_eq!(dur.longest, 3);
+    assert_eq!((dur.start, dur.end), (quotes[1].ts, quotes[5].ts));
+    assert!(dur.recovered);
+    assert_eq!(dur.underwater_since, Some(quotes[5].ts));
+
+    let dur = calc_drawdown_duration(&quotes[..6]).unwrap();
+    assert_eq!(dur.underwater_since, None);
+    let dur = calc_drawdown_duration(&quotes[..4]).unwrap();
+    assert_eq!(
+        (dur.longest, dur.end, dur.recovered),
+        (2, quotes[3].ts, false)
+    );
+
+    // never below an earlier close
+    assert_eq!(
+        calc_drawdown_duration(&from_closes(&[100.0, 101.0, 101.0])),
+        None
+    );
+    let out = run(&["-t", "late"]);
+    assert!(out.contains("Longest drawdown: none"));
+    let out = run(&["-t", "test"]);
+    assert!(out.contains("Longest drawdown: 1 candle (2024-01-03\u{2013}2024-01-05)"));
+}
+
+#[test]
+fn backtest_fills_crossovers_at_the_next_open() {
+    // closes cross above their 2-period SMA on the 4th candle, below on the 6th and above again
+    // on the last, which is too late to fill
+    let quotes = from_closes(&[10.0, 9.0, 8.0, 10.0, 12.0, 11.0, 9.0, 10.0]);
+    let bt = calc_backtest(&quotes, PriceType::Close, 1, 2, 0.0);
+    assert_eq!(bt.trades.len(), 1);
+    let trade = &bt.trades[0];
+    assert_eq!(
+        (trade.entry, trade.exit),
+        (quotes[4].ts, Some(quotes[6].ts))
+    );
+    assert_eq!((trade.entry_price, trade.exit_price), (10.0, 11.0));
+    assert_close(bt.total_return, 10.0);
+    assert_eq!(bt.win_rate, Some(100.0));
+    // equity peaked at 1.2 on the 5th candle and closed the trade at 1.1
+    assert_close(bt.max_drawdown, 100.0 / 12.0);
+    assert_eq!((bt.hold_return, bt.hold_max_drawdown), (0.0, 25.0));
+
+    // 1% on the way in and the way out
+    let bt = calc_backtest(&quotes, PriceType::Close, 1, 2, 0.01);
+    assert_close(bt.total_return, 7.811);
+
+    // flat closes never cross, but a spike in the high lifts the typical price on the 3rd
+    let mut spike = vec![(10.0, 10.0, 10.0, 10.0); 5];
+    spike[2].1 = 16.0;
+    let quotes = synthetic(&spike);
+    assert!(calc_backtest(&quotes, PriceType::Close, 1, 2, 0.0)
+        .trades
+        .is_empty());
+    let bt = calc_backtest(&quotes, PriceType::Typical, 1, 2, 0.0);
+    assert_eq!(
+        (bt.trades[0].entry, bt.trades[0].exit),
+        (quotes[3].ts, Some(quotes[4].ts))
+    );
+}
+
+#[test]
+fn money_flow_index_edges() {
+    // no typical-price change, so no flow either way: the midpoint
+    let flat = calc_mfi(&from_closes(&[100.0; 6]), 3);
+    assert_eq!((flat.value, flat.skipped), (Some(50.0), 0));
+    // only inflows, with no negative flow to divide by
+    let mut rising = from_closes(&[100.0, 101.0, 102.0, 103.0, 104.0, 105.0]);
+    assert_eq!(calc_mfi(&rising, 3).value, Some(100.0));
+    // candles without volume are dropped and counted, and the period needs period + 1 left
+    rising[4].volume = None;
+    rising[5].volume = None;
+    let mfi = calc_mfi(&rising, 3);
+    assert_eq!((mfi.value, mfi.skipped), (Some(100.0), 2));
+    let mfi = calc_mfi(&rising, 4);
+    assert_eq!((mfi.value, mfi.skipped), (None, 2));
+}
+
+#[test]
+fn earnings_markers_snap_to_the_next_candle() {
+    // Fri 5th, Mon 8th and Tue 9th
+    let day = |d| Utc.with_ymd_and_hms(2024, 1, d, 14, 30, 0).unwrap();
+    let mut quotes = from_closes(&[100.0; 3]);
+    for (q, d) in quotes.iter_mut().zip([5, 8, 9]) {
+        q.ts = day(d);
+    }
+    let candle = |earnings| earnings_candle(&quotes, Some(DEFAULT_TZ), &earnings);
+    // after the close on the 5th, on a Saturday, before and after the window
+    assert_eq!(
+        candle(Utc.with_ymd_and_hms(2024, 1, 5, 21, 0, 0).unwrap()),
+        Some(0)
+    );
+    assert_eq!(candle(day(6)), Some(1));
+    assert_eq!(candle(day(2)), None);
+    assert_eq!(candle(day(12)), None);
+}
+
+#[test]
+fn earnings_match_candles_in_exchange_time() {
+    // the close on the 2nd and 3rd, a post-market bar at 20:30 ET on the 3rd (already the 4th in
+    // UTC), then the open on the 4th
+    let mut quotes = from_closes(&[100.0, 101.0, 102.0, 103.0]);
+    quotes[2].ts = Utc.with_ymd_and_hms(2024, 1, 4, 1, 30, 0).unwrap();
+    quotes[3].ts = Utc.with_ymd_and_hms(2024, 1, 4, 14, 30, 0).unwrap();
+    // a pre-market report on the 4th lands on the 4th's open, not the evening bar
+    let earnings = Utc.with_ymd_and_hms(2024, 1, 4, 12, 0, 0).unwrap();
+    assert_eq!(
+        earnings_candle(&quotes, Some(DEFAULT_TZ), &earnings),
+        Some(3)
+    );
+    // and a report after the close on the 3rd on that evening's bar
+    let earnings = Utc.with_ymd_and_hms(2024, 1, 3, 21, 0, 0).unwrap();
+    assert_eq!(
+        earnings_candle(&quotes, Some(DEFAULT_TZ), &earnings),
+        Some(1)
+    );
+}
+
+#[test]
+fn relative_volume_over_the_last_window() {
+    // every candle trades 1000 apart from the latest
+    let mut quotes = from_closes(&[100.0, 101.0, 102.0, 103.0, 104.0]);
+    quotes[4].volume = Some(3000);
+    let rv = calc_relative_volume(&quotes, 3);
+    assert_eq!((rv.value, rv.skipped), (Some(3.0), 0));
+    // a window candle without volume is left out of the average, not replaced by an older one
+    quotes[0].volume = Some(10_000);
+    quotes[2].volume = None;
+    let rv = calc_relative_volume(&quotes, 3);
+    assert_eq!((rv.value, rv.skipped), (Some(3.0), 1));
+    quotes[1].volume = None;
+    quotes[3].volume = None;
+    let rv = calc_relative_volume(&quotes, 3);
+    assert_eq!((rv.value, rv.skipped), (None, 3));
+    assert_eq!(calc_relative_volume(&quotes, 5).value, None);
+}
+
+#[test]
+fn omega_without_downside_is_infinite() {
+    // LATE only ever closes higher
+    let out = run(&["-t", "late"]);
+    assert!(out.contains("Omega ratio: inf (threshold 0.00%)"));
+    // JSON has no infinity, so it becomes null
+    let out = run(&["-t", "late", "-f", "jsonl"]);
+    assert!(out.contains("\"omega\":null"));
+}